@@ -0,0 +1,98 @@
+// Derive macros for Neovide's settings. They expand to paths inside the neovide crate, so they are
+// only meant to be used from there.
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields, Lit, Meta, NestedMeta, Variant};
+
+// Implements FromValue and IntoValue for an enum of unit variants, each stored in nvim as a
+// string. The string is the lowercased variant name unless `#[setting(rename = "...")]` says
+// otherwise.
+#[proc_macro_derive(SettingEnum, attributes(setting))]
+pub fn derive_setting_enum(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_setting_enum(input)
+        .unwrap_or_else(|error| error.to_compile_error())
+        .into()
+}
+
+fn expand_setting_enum(input: DeriveInput) -> Result<proc_macro2::TokenStream, Error> {
+    let enum_name = &input.ident;
+    let variants = match &input.data {
+        Data::Enum(data) => &data.variants,
+        _ => {
+            return Err(Error::new(
+                Span::call_site(),
+                "SettingEnum can only be derived for enums",
+            ))
+        }
+    };
+
+    let mut idents = Vec::new();
+    let mut names = Vec::new();
+    for variant in variants.iter() {
+        if !matches!(variant.fields, Fields::Unit) {
+            return Err(Error::new_spanned(
+                variant,
+                "SettingEnum variants can't have fields",
+            ));
+        }
+        idents.push(&variant.ident);
+        names.push(setting_name(variant)?);
+    }
+
+    let expected = names
+        .iter()
+        .map(|name| format!("\"{}\"", name))
+        .collect::<Vec<String>>()
+        .join(", ");
+
+    Ok(quote! {
+        impl crate::settings::FromValue for #enum_name {
+            fn from_value(value: crate::settings::Value) -> Result<Self, String> {
+                match value.as_str() {
+                    #(Some(#names) => Ok(#enum_name::#idents),)*
+                    _ => Err(format!("expected one of {}, but received {}", #expected, value)),
+                }
+            }
+        }
+
+        impl crate::settings::IntoValue for #enum_name {
+            fn into_value(self) -> crate::settings::Value {
+                match self {
+                    #(#enum_name::#idents => crate::settings::Value::from(#names),)*
+                }
+            }
+        }
+    })
+}
+
+fn setting_name(variant: &Variant) -> Result<String, Error> {
+    for attribute in variant.attrs.iter() {
+        if !attribute.path.is_ident("setting") {
+            continue;
+        }
+
+        if let Meta::List(list) = attribute.parse_meta()? {
+            for nested in list.nested.iter() {
+                if let NestedMeta::Meta(Meta::NameValue(name_value)) = nested {
+                    if name_value.path.is_ident("rename") {
+                        if let Lit::Str(name) = &name_value.lit {
+                            return Ok(name.value());
+                        }
+                    }
+                }
+            }
+        }
+
+        return Err(Error::new_spanned(
+            attribute,
+            "expected #[setting(rename = \"...\")]",
+        ));
+    }
+
+    Ok(variant.ident.to_string().to_lowercase())
+}
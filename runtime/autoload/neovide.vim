@@ -0,0 +1,148 @@
+" Vimscript wrappers around Neovide's runtime api. See lua/neovide/init.lua.
+
+function! s:request(method, ...) abort
+  if !exists('g:neovide_channel')
+    throw 'neovide: no Neovide window is attached'
+  endif
+  return call('rpcrequest', [g:neovide_channel, 'neovide.' . a:method] + a:000)
+endfunction
+
+function! neovide#set_font(name, ...) abort
+  return call('s:request', ['set_font', a:name] + a:000)
+endfunction
+
+function! neovide#screenshot(path) abort
+  return s:request('screenshot', a:path)
+endfunction
+
+function! neovide#zoom(factor) abort
+  return s:request('zoom', a:factor)
+endfunction
+
+function! neovide#toggle_fullscreen() abort
+  return s:request('toggle_fullscreen')
+endfunction
+
+function! neovide#get_metrics() abort
+  return s:request('get_metrics')
+endfunction
+
+function! neovide#get_cell_size() abort
+  return s:request('get_cell_size')
+endfunction
+
+function! neovide#get_window_size() abort
+  return s:request('get_window_size')
+endfunction
+
+function! neovide#get_scale_factor() abort
+  return s:request('get_scale_factor')
+endfunction
+
+function! neovide#get_font() abort
+  return s:request('get_font')
+endfunction
+
+function! neovide#get_refresh_rate() abort
+  return s:request('get_refresh_rate')
+endfunction
+
+function! neovide#pause_animations() abort
+  return s:request('pause_animations')
+endfunction
+
+function! neovide#resume_animations() abort
+  return s:request('resume_animations')
+endfunction
+
+function! neovide#step_animations(seconds) abort
+  return s:request('step_animations', a:seconds)
+endfunction
+
+function! neovide#set_particle_seed(seed) abort
+  return s:request('set_particle_seed', a:seed)
+endfunction
+
+function! neovide#image_show(id, source, grid, row, col, width_cells, height_cells) abort
+  return s:request('image_show', a:id, a:source, a:grid, a:row, a:col, a:width_cells, a:height_cells)
+endfunction
+
+function! neovide#image_hide(id) abort
+  return s:request('image_hide', a:id)
+endfunction
+
+function! neovide#list_settings() abort
+  return s:request('list_settings')
+endfunction
+
+function! neovide#complete_settings(arg_lead, cmd_line, cursor_pos) abort
+  let names = map(neovide#list_settings(), 'v:val.name')
+  return filter(names, 'stridx(v:val, a:arg_lead) == 0')
+endfunction
+
+" Backs :NeovideSettings. Without an argument every setting is listed, otherwise only the ones
+" whose name contains it.
+function! neovide#print_settings(filter) abort
+  for setting in neovide#list_settings()
+    if a:filter ==# '' || stridx(setting.name, a:filter) >= 0
+      echo printf('g:neovide_%s = %s (default %s, %s)',
+            \ setting.name, string(setting.value), string(setting.default), setting.type)
+    endif
+  endfor
+endfunction
+
+" Sends the b: and w: overrides of the given settings to Neovide. Window variables take precedence
+" over buffer variables. Called whenever the cursor enters another buffer or window, and whenever
+" an override of the current buffer or window changes.
+function! neovide#notify_overrides(channel, names) abort
+  let s:override_channel = a:channel
+  let s:override_names = a:names
+  call s:watch_overrides(b:)
+  call s:watch_overrides(w:)
+
+  let overrides = {}
+  for name in a:names
+    let variable = 'neovide_' . name
+    if has_key(w:, variable)
+      let overrides[name] = w:[variable]
+    elseif has_key(b:, variable)
+      let overrides[name] = b:[variable]
+    endif
+  endfor
+  call rpcnotify(a:channel, 'setting_overrides', overrides)
+endfunction
+
+" The marker doesn't match neovide_*, so setting it doesn't trigger the watcher itself.
+function! s:watch_overrides(scope) abort
+  if !has_key(a:scope, '_neovide_watched')
+    let a:scope._neovide_watched = v:true
+    call dictwatcheradd(a:scope, 'neovide_*', function('s:on_override_changed'))
+  endif
+endfunction
+
+" Changes to buffers and windows other than the current one are picked up once they are entered.
+function! s:on_override_changed(scope, key, change) abort
+  if a:scope is b: || a:scope is w:
+    call neovide#notify_overrides(s:override_channel, s:override_names)
+  endif
+endfunction
+
+" Nvim started by Neovide holds on to a pipe from it. The pipe closes when Neovide goes away, and
+" nvim quits along with it unless the ui was detached on purpose. Needs stdioopen() from nvim 0.5.
+let s:detached = v:false
+
+function! neovide#quit_with_neovide() abort
+  if exists('*stdioopen')
+    call stdioopen({'on_stdin': function('s:on_stdin')})
+  endif
+endfunction
+
+function! s:on_stdin(channel, data, name) abort
+  if a:data == [''] && !s:detached
+    qall!
+  endif
+endfunction
+
+function! neovide#detach() abort
+  let s:detached = v:true
+endfunction
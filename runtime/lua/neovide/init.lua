@@ -0,0 +1,102 @@
+-- Neovide's runtime api. Neovide adds this directory to the runtimepath when it attaches, so
+-- plugins can check for Neovide with `vim.g.neovide` and then require('neovide').
+--
+-- Every function is an rpc request to the Neovide window attached to this nvim.
+local M = {}
+
+local function request(method, ...)
+  local channel = vim.g.neovide_channel
+  if channel == nil then
+    error('neovide: no Neovide window is attached')
+  end
+  return vim.rpcrequest(channel, 'neovide.' .. method, ...)
+end
+
+-- Sets the font without touching 'guifont'. The size is optional.
+function M.set_font(name, size)
+  return request('set_font', name, size)
+end
+
+-- Writes the current grid contents to the given path as a png and returns the path.
+function M.screenshot(path)
+  return request('screenshot', path)
+end
+
+-- Scales the font by the given factor. 1.0 is the unzoomed size.
+function M.zoom(factor)
+  return request('zoom', factor)
+end
+
+-- Returns whether the window is fullscreen after toggling.
+function M.toggle_fullscreen()
+  return request('toggle_fullscreen')
+end
+
+-- Returns a table with the cell size, grid size and zoom of the window.
+function M.get_metrics()
+  return request('get_metrics')
+end
+
+-- Returns the size of a grid cell in logical pixels as { width = ..., height = ... }.
+function M.get_cell_size()
+  return request('get_cell_size')
+end
+
+-- Returns the window size as { logical = { width, height }, physical = { width, height } }.
+function M.get_window_size()
+  return request('get_window_size')
+end
+
+-- Returns the ratio between physical and logical pixels.
+function M.get_scale_factor()
+  return request('get_scale_factor')
+end
+
+-- Returns the font currently in use as { name = ..., size = ... }.
+function M.get_font()
+  return request('get_font')
+end
+
+-- Returns the refresh rate in frames per second.
+function M.get_refresh_rate()
+  return request('get_refresh_rate')
+end
+
+-- Freezes cursor animations, blinking and particles in every window until they are resumed.
+function M.pause_animations()
+  return request('pause_animations')
+end
+
+function M.resume_animations()
+  return request('resume_animations')
+end
+
+-- Advances paused animations by the given number of seconds.
+function M.step_animations(seconds)
+  return request('step_animations', seconds)
+end
+
+-- Reseeds the cursor particles so that the effects repeat exactly for the same frames.
+function M.set_particle_seed(seed)
+  return request('set_particle_seed', seed)
+end
+
+-- Returns every setting as a list of { name, value, default, type } tables sorted by name. The
+-- names are the g:neovide_ variables without their prefix.
+function M.list_settings()
+  return request('list_settings')
+end
+
+-- Shows an image over the given grid. The source is a file path or the encoded image bytes. The
+-- position and size are in cells and the image moves when the grid scrolls. Showing an image with
+-- an id which is already in use replaces it.
+function M.image_show(id, source, grid, row, col, width_cells, height_cells)
+  return request('image_show', id, source, grid, row, col, width_cells, height_cells)
+end
+
+-- Removes the image with the given id.
+function M.image_hide(id)
+  return request('image_hide', id)
+end
+
+return M
@@ -0,0 +1,197 @@
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::process::Stdio;
+
+use futures::io::AsyncWrite;
+use log::error;
+use nvim_rs::compat::tokio::Compat;
+use nvim_rs::error::{CallError, LoopError};
+use nvim_rs::Neovim;
+use rmpv::Value;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::process::{Child, ChildStderr};
+use tokio::task::JoinHandle;
+
+use super::create_nvim_command;
+use super::handler::NeovimHandler;
+use crate::command_line;
+use crate::paths::{private_directory, runtime_directory};
+
+// Writer half of the rpc connection. Boxed so that tcp, unix sockets and named pipes can share the
+// same Neovim type.
+pub type NeovimWriter = Box<dyn AsyncWrite + Send + Unpin>;
+pub type IoHandle = JoinHandle<Result<(), Box<LoopError>>>;
+
+fn spawn_io_loop<R>(
+    reader: R,
+    writer: NeovimWriter,
+    handler: NeovimHandler,
+) -> (Neovim<NeovimWriter>, IoHandle)
+where
+    R: futures::io::AsyncRead + Send + Unpin + 'static,
+{
+    let (nvim, io) = Neovim::new(reader, writer, handler);
+    (nvim, tokio::spawn(io))
+}
+
+// Nvim is started as a headless server rather than embedded on our stdio, so that it can keep
+// running on its own once the ui detaches. Before loading any configuration it reports the
+// address it listens on through stderr. The rest of stderr is handed back to be captured.
+pub async fn start_child(
+    handler: NeovimHandler,
+    arguments: &[String],
+) -> io::Result<(
+    Neovim<NeovimWriter>,
+    IoHandle,
+    Child,
+    BufReader<ChildStderr>,
+)> {
+    let mut child = create_nvim_command(arguments, &report_address_command())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()?;
+
+    let missing_pipe = || io::Error::new(ErrorKind::Other, "Could not open neovim's stderr pipe");
+    let mut stderr = BufReader::new(child.stderr.take().ok_or_else(missing_pipe)?);
+
+    let mut address = String::new();
+    stderr.read_line(&mut address).await?;
+    let address = address.trim();
+
+    match connect_to_address(address, handler).await {
+        Ok((nvim, io_handle)) => Ok((nvim, io_handle, child, stderr)),
+        Err(error) => {
+            // Whatever nvim printed instead of an address usually says why it didn't start
+            child.kill().ok();
+            Err(io::Error::new(
+                error.kind(),
+                format!("{} (neovim reported: {})", error, address),
+            ))
+        }
+    }
+}
+
+// Nvim picks the address itself: a unix socket in a private directory, or a named pipe on
+// Windows. Unlike a tcp port, other users can't send requests to either.
+const LISTEN_EXPRESSION: &str = "serverstart()";
+
+fn report_address_command() -> String {
+    format!("call chansend(v:stderr, {} . \"\\n\")", LISTEN_EXPRESSION)
+}
+
+fn is_attachable(address: &str) -> bool {
+    !address.is_empty()
+}
+
+// Connects to an nvim which is already running and listening on the given address. Addresses of
+// the form ip:port are connected to over tcp, anything else is treated as a unix socket path or,
+// on Windows, a named pipe.
+pub async fn connect_to_address(
+    address: &str,
+    handler: NeovimHandler,
+) -> io::Result<(Neovim<NeovimWriter>, IoHandle)> {
+    if let Ok(socket_address) = address.parse::<SocketAddr>() {
+        let stream = TcpStream::connect(socket_address).await?;
+        let (reader, writer) = tokio::io::split(stream);
+        return Ok(spawn_io_loop(
+            Compat::new(reader),
+            Box::new(Compat::new(writer)),
+            handler,
+        ));
+    }
+
+    connect_to_socket(address, handler).await
+}
+
+#[cfg(unix)]
+async fn connect_to_socket(
+    address: &str,
+    handler: NeovimHandler,
+) -> io::Result<(Neovim<NeovimWriter>, IoHandle)> {
+    let stream = tokio::net::UnixStream::connect(address).await?;
+    let (reader, writer) = tokio::io::split(stream);
+    Ok(spawn_io_loop(
+        Compat::new(reader),
+        Box::new(Compat::new(writer)),
+        handler,
+    ))
+}
+
+// Named pipes are opened like files. Opening them for overlapped io lets tokio drive them.
+#[cfg(windows)]
+async fn connect_to_socket(
+    address: &str,
+    handler: NeovimHandler,
+) -> io::Result<(Neovim<NeovimWriter>, IoHandle)> {
+    use std::os::windows::fs::OpenOptionsExt;
+    use std::os::windows::io::{FromRawHandle, IntoRawHandle};
+
+    use mio_named_pipes::NamedPipe;
+    use tokio::io::PollEvented;
+
+    const FILE_FLAG_OVERLAPPED: u32 = 0x40000000;
+
+    let file = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(FILE_FLAG_OVERLAPPED)
+        .open(address)?;
+    let pipe = unsafe { NamedPipe::from_raw_handle(file.into_raw_handle()) };
+    let stream = PollEvented::new(pipe)?;
+    let (reader, writer) = tokio::io::split(stream);
+    Ok(spawn_io_loop(
+        Compat::new(reader),
+        Box::new(Compat::new(writer)),
+        handler,
+    ))
+}
+
+// Remembers where the most recently detached session is listening so that a bare --attach can
+// find it again.
+fn detached_session_file() -> io::Result<PathBuf> {
+    let runtime_directory = runtime_directory()
+        .ok_or_else(|| io::Error::new(ErrorKind::NotFound, "Could not find a runtime directory"))?;
+    Ok(private_directory(&runtime_directory)?.join("detached_session"))
+}
+
+pub fn remember_detached_session(address: &str) {
+    if let Err(error) = detached_session_file().and_then(|file| fs::write(file, address)) {
+        error!("Could not remember the detached session: {}", error);
+    }
+}
+
+// Address passed with --attach <address>. A bare --attach reattaches to the most recently
+// detached session.
+pub fn attach_address() -> Option<String> {
+    match command_line::option_value("--attach")? {
+        Some(address) => Some(address),
+        None => detached_session_file()
+            .and_then(fs::read_to_string)
+            .ok()
+            .map(|address| address.trim().to_string()),
+    }
+}
+
+// Address other Neovide instances can use to attach to this nvim after detaching. Nvim started
+// by Neovide already listens on the address it reported, anything else is asked to listen first.
+pub async fn server_address(nvim: &Neovim<NeovimWriter>) -> Result<String, Box<CallError>> {
+    let servers = nvim.call_function("serverlist", vec![]).await?;
+    let listening = servers.as_array().and_then(|servers| {
+        servers
+            .iter()
+            .filter_map(Value::as_str)
+            .find(|address| is_attachable(address))
+            .map(String::from)
+    });
+
+    match listening {
+        Some(address) => Ok(address),
+        None => {
+            let address = nvim.eval(LISTEN_EXPRESSION).await?;
+            Ok(address.as_str().unwrap_or_default().to_string())
+        }
+    }
+}
@@ -1,13 +1,15 @@
 use std::convert::TryInto;
 use std::error;
 use std::fmt;
+use std::sync::Arc;
 
+use parking_lot::Mutex;
 use rmpv::Value;
 use skulpin::skia_safe::Color4f;
 
-use crate::editor::EDITOR;
-use crate::editor::{Colors, CursorMode, CursorShape, Style};
-use crate::error_handling::ResultPanicExplanation;
+use crate::crash_report::CRASH_REPORTER;
+use crate::editor::{Colors, CursorMode, CursorShape, Editor, Style};
+use crate::error_handling::report_recoverable_error;
 
 #[derive(Debug, Clone)]
 pub enum EventParseError {
@@ -274,13 +276,15 @@ fn unpack_color(packed_color: u64) -> Color4f {
     }
 }
 
-fn extract_values<Arr: AsMut<[Value]>>(values: Vec<Value>, mut arr: Arr) -> Result<Arr> {
+// The parsers only borrow the event values, so that the raw event is still around to be logged or
+// recorded in a crash report when something goes wrong.
+fn extract_values<'a, Arr: AsMut<[&'a Value]>>(values: &'a [Value], mut arr: Arr) -> Result<Arr> {
     let arr_ref = arr.as_mut();
 
     if values.len() != arr_ref.len() {
         Err(EventParseError::InvalidEventFormat)
     } else {
-        for (i, val) in values.into_iter().enumerate() {
+        for (i, val) in values.iter().enumerate() {
             arr_ref[i] = val;
         }
 
@@ -288,45 +292,56 @@ fn extract_values<Arr: AsMut<[Value]>>(values: Vec<Value>, mut arr: Arr) -> Resu
     }
 }
 
-fn parse_array(array_value: Value) -> Result<Vec<Value>> {
+fn parse_array(array_value: &Value) -> Result<&[Value]> {
     array_value
-        .try_into()
-        .map_err(EventParseError::InvalidArray)
+        .as_array()
+        .map(Vec::as_slice)
+        .ok_or_else(|| EventParseError::InvalidArray(array_value.clone()))
 }
 
-fn parse_map(map_value: Value) -> Result<Vec<(Value, Value)>> {
-    map_value.try_into().map_err(EventParseError::InvalidMap)
+fn parse_map(map_value: &Value) -> Result<&[(Value, Value)]> {
+    map_value
+        .as_map()
+        .map(Vec::as_slice)
+        .ok_or_else(|| EventParseError::InvalidMap(map_value.clone()))
 }
 
-fn parse_string(string_value: Value) -> Result<String> {
+fn parse_string(string_value: &Value) -> Result<String> {
     string_value
-        .try_into()
-        .map_err(EventParseError::InvalidString)
+        .as_str()
+        .map(String::from)
+        .ok_or_else(|| EventParseError::InvalidString(string_value.clone()))
 }
 
-fn parse_u64(u64_value: Value) -> Result<u64> {
-    u64_value.try_into().map_err(EventParseError::InvalidU64)
+fn parse_u64(u64_value: &Value) -> Result<u64> {
+    u64_value
+        .as_u64()
+        .ok_or_else(|| EventParseError::InvalidU64(u64_value.clone()))
 }
 
-fn parse_i64(i64_value: Value) -> Result<i64> {
-    i64_value.try_into().map_err(EventParseError::InvalidI64)
+fn parse_i64(i64_value: &Value) -> Result<i64> {
+    i64_value
+        .as_i64()
+        .ok_or_else(|| EventParseError::InvalidI64(i64_value.clone()))
 }
 
-fn parse_bool(bool_value: Value) -> Result<bool> {
-    bool_value.try_into().map_err(EventParseError::InvalidBool)
+fn parse_bool(bool_value: &Value) -> Result<bool> {
+    bool_value
+        .as_bool()
+        .ok_or_else(|| EventParseError::InvalidBool(bool_value.clone()))
 }
 
-fn parse_set_title(set_title_arguments: Vec<Value>) -> Result<RedrawEvent> {
-    let [title] = extract_values(set_title_arguments, [Value::Nil])?;
+fn parse_set_title(set_title_arguments: &[Value]) -> Result<RedrawEvent> {
+    let [title] = extract_values(set_title_arguments, [&Value::Nil])?;
 
     Ok(RedrawEvent::SetTitle {
         title: parse_string(title)?,
     })
 }
 
-fn parse_mode_info_set(mode_info_set_arguments: Vec<Value>) -> Result<RedrawEvent> {
+fn parse_mode_info_set(mode_info_set_arguments: &[Value]) -> Result<RedrawEvent> {
     let [_cursor_style_enabled, mode_info] =
-        extract_values(mode_info_set_arguments, [Value::Nil, Value::Nil])?;
+        extract_values(mode_info_set_arguments, [&Value::Nil, &Value::Nil])?;
 
     let mode_info_values = parse_array(mode_info)?;
     let mut cursor_modes = Vec::with_capacity(mode_info_values.len());
@@ -365,8 +380,8 @@ fn parse_mode_info_set(mode_info_set_arguments: Vec<Value>) -> Result<RedrawEven
     Ok(RedrawEvent::ModeInfoSet { cursor_modes })
 }
 
-fn parse_option_set(option_set_arguments: Vec<Value>) -> Result<RedrawEvent> {
-    let [name, value] = extract_values(option_set_arguments, [Value::Nil, Value::Nil])?;
+fn parse_option_set(option_set_arguments: &[Value]) -> Result<RedrawEvent> {
+    let [name, value] = extract_values(option_set_arguments, [&Value::Nil, &Value::Nil])?;
 
     let name = parse_string(name)?;
 
@@ -382,13 +397,13 @@ fn parse_option_set(option_set_arguments: Vec<Value>) -> Result<RedrawEvent> {
             "pumblend" => GuiOption::Pumblend(parse_u64(value)?),
             "showtabline" => GuiOption::ShowTabLine(parse_u64(value)?),
             "termguicolors" => GuiOption::TermGuiColors(parse_bool(value)?),
-            _ => GuiOption::Unknown(name, value),
+            _ => GuiOption::Unknown(name, value.clone()),
         },
     })
 }
 
-fn parse_mode_change(mode_change_arguments: Vec<Value>) -> Result<RedrawEvent> {
-    let [mode, mode_index] = extract_values(mode_change_arguments, [Value::Nil, Value::Nil])?;
+fn parse_mode_change(mode_change_arguments: &[Value]) -> Result<RedrawEvent> {
+    let [mode, mode_index] = extract_values(mode_change_arguments, [&Value::Nil, &Value::Nil])?;
     let mode_name = parse_string(mode)?;
 
     Ok(RedrawEvent::ModeChange {
@@ -403,9 +418,11 @@ fn parse_mode_change(mode_change_arguments: Vec<Value>) -> Result<RedrawEvent> {
     })
 }
 
-fn parse_grid_resize(grid_resize_arguments: Vec<Value>) -> Result<RedrawEvent> {
-    let [grid_id, width, height] =
-        extract_values(grid_resize_arguments, [Value::Nil, Value::Nil, Value::Nil])?;
+fn parse_grid_resize(grid_resize_arguments: &[Value]) -> Result<RedrawEvent> {
+    let [grid_id, width, height] = extract_values(
+        grid_resize_arguments,
+        [&Value::Nil, &Value::Nil, &Value::Nil],
+    )?;
 
     Ok(RedrawEvent::Resize {
         grid: parse_u64(grid_id)?,
@@ -414,8 +431,14 @@ fn parse_grid_resize(grid_resize_arguments: Vec<Value>) -> Result<RedrawEvent> {
     })
 }
 
-fn parse_default_colors(default_colors_arguments: Vec<Value>) -> Result<RedrawEvent> {
-    let values = [Value::Nil, Value::Nil, Value::Nil, Value::Nil, Value::Nil];
+fn parse_default_colors(default_colors_arguments: &[Value]) -> Result<RedrawEvent> {
+    let values = [
+        &Value::Nil,
+        &Value::Nil,
+        &Value::Nil,
+        &Value::Nil,
+        &Value::Nil,
+    ];
     let [foreground, background, special, _term_foreground, _term_background] =
         extract_values(default_colors_arguments, values)?;
 
@@ -428,7 +451,7 @@ fn parse_default_colors(default_colors_arguments: Vec<Value>) -> Result<RedrawEv
     })
 }
 
-fn parse_style(style_map: Value) -> Result<Style> {
+fn parse_style(style_map: &Value) -> Result<Style> {
     let attributes = parse_map(style_map)?;
 
     let mut style = Style::new(Colors::new(None, None, None));
@@ -445,14 +468,14 @@ fn parse_style(style_map: Value) -> Result<Style> {
                 ("special", Value::Integer(packed_color)) => {
                     style.colors.special = Some(unpack_color(packed_color.as_u64().unwrap()))
                 }
-                ("reverse", Value::Boolean(reverse)) => style.reverse = reverse,
-                ("italic", Value::Boolean(italic)) => style.italic = italic,
-                ("bold", Value::Boolean(bold)) => style.bold = bold,
+                ("reverse", Value::Boolean(reverse)) => style.reverse = *reverse,
+                ("italic", Value::Boolean(italic)) => style.italic = *italic,
+                ("bold", Value::Boolean(bold)) => style.bold = *bold,
                 ("strikethrough", Value::Boolean(strikethrough)) => {
-                    style.strikethrough = strikethrough
+                    style.strikethrough = *strikethrough
                 }
-                ("underline", Value::Boolean(underline)) => style.underline = underline,
-                ("undercurl", Value::Boolean(undercurl)) => style.undercurl = undercurl,
+                ("underline", Value::Boolean(underline)) => style.underline = *underline,
+                ("undercurl", Value::Boolean(undercurl)) => style.undercurl = *undercurl,
                 ("blend", Value::Integer(blend)) => style.blend = blend.as_u64().unwrap() as u8,
                 _ => println!("Ignored style attribute: {}", name),
             }
@@ -464,8 +487,8 @@ fn parse_style(style_map: Value) -> Result<Style> {
     Ok(style)
 }
 
-fn parse_hl_attr_define(hl_attr_define_arguments: Vec<Value>) -> Result<RedrawEvent> {
-    let values = [Value::Nil, Value::Nil, Value::Nil, Value::Nil];
+fn parse_hl_attr_define(hl_attr_define_arguments: &[Value]) -> Result<RedrawEvent> {
+    let values = [&Value::Nil, &Value::Nil, &Value::Nil, &Value::Nil];
     let [id, attributes, _terminal_attributes, _info] =
         extract_values(hl_attr_define_arguments, values)?;
 
@@ -476,28 +499,15 @@ fn parse_hl_attr_define(hl_attr_define_arguments: Vec<Value>) -> Result<RedrawEv
     })
 }
 
-fn parse_grid_line_cell(grid_line_cell: Value) -> Result<GridLineCell> {
-    fn take_value(val: &mut Value) -> Value {
-        std::mem::replace(val, Value::Nil)
-    }
-
-    let mut cell_contents = parse_array(grid_line_cell)?;
+fn parse_grid_line_cell(grid_line_cell: &Value) -> Result<GridLineCell> {
+    let cell_contents = parse_array(grid_line_cell)?;
 
     let text_value = cell_contents
-        .first_mut()
-        .map(|v| take_value(v))
+        .first()
         .ok_or(EventParseError::InvalidEventFormat)?;
 
-    let highlight_id = cell_contents
-        .get_mut(1)
-        .map(|v| take_value(v))
-        .map(parse_u64)
-        .transpose()?;
-    let repeat = cell_contents
-        .get_mut(2)
-        .map(|v| take_value(v))
-        .map(parse_u64)
-        .transpose()?;
+    let highlight_id = cell_contents.get(1).map(parse_u64).transpose()?;
+    let repeat = cell_contents.get(2).map(parse_u64).transpose()?;
 
     Ok(GridLineCell {
         text: parse_string(text_value)?,
@@ -506,8 +516,8 @@ fn parse_grid_line_cell(grid_line_cell: Value) -> Result<GridLineCell> {
     })
 }
 
-fn parse_grid_line(grid_line_arguments: Vec<Value>) -> Result<RedrawEvent> {
-    let values = [Value::Nil, Value::Nil, Value::Nil, Value::Nil];
+fn parse_grid_line(grid_line_arguments: &[Value]) -> Result<RedrawEvent> {
+    let values = [&Value::Nil, &Value::Nil, &Value::Nil, &Value::Nil];
     let [grid_id, row, column_start, cells] = extract_values(grid_line_arguments, values)?;
 
     Ok(RedrawEvent::GridLine {
@@ -515,23 +525,25 @@ fn parse_grid_line(grid_line_arguments: Vec<Value>) -> Result<RedrawEvent> {
         row: parse_u64(row)?,
         column_start: parse_u64(column_start)?,
         cells: parse_array(cells)?
-            .into_iter()
+            .iter()
             .map(parse_grid_line_cell)
             .collect::<Result<Vec<GridLineCell>>>()?,
     })
 }
 
-fn parse_clear(clear_arguments: Vec<Value>) -> Result<RedrawEvent> {
-    let [grid_id] = extract_values(clear_arguments, [Value::Nil])?;
+fn parse_clear(clear_arguments: &[Value]) -> Result<RedrawEvent> {
+    let [grid_id] = extract_values(clear_arguments, [&Value::Nil])?;
 
     Ok(RedrawEvent::Clear {
         grid: parse_u64(grid_id)?,
     })
 }
 
-fn parse_cursor_goto(cursor_goto_arguments: Vec<Value>) -> Result<RedrawEvent> {
-    let [grid_id, column, row] =
-        extract_values(cursor_goto_arguments, [Value::Nil, Value::Nil, Value::Nil])?;
+fn parse_cursor_goto(cursor_goto_arguments: &[Value]) -> Result<RedrawEvent> {
+    let [grid_id, column, row] = extract_values(
+        cursor_goto_arguments,
+        [&Value::Nil, &Value::Nil, &Value::Nil],
+    )?;
 
     Ok(RedrawEvent::CursorGoto {
         grid: parse_u64(grid_id)?,
@@ -540,15 +552,15 @@ fn parse_cursor_goto(cursor_goto_arguments: Vec<Value>) -> Result<RedrawEvent> {
     })
 }
 
-fn parse_grid_scroll(grid_scroll_arguments: Vec<Value>) -> Result<RedrawEvent> {
+fn parse_grid_scroll(grid_scroll_arguments: &[Value]) -> Result<RedrawEvent> {
     let values = [
-        Value::Nil,
-        Value::Nil,
-        Value::Nil,
-        Value::Nil,
-        Value::Nil,
-        Value::Nil,
-        Value::Nil,
+        &Value::Nil,
+        &Value::Nil,
+        &Value::Nil,
+        &Value::Nil,
+        &Value::Nil,
+        &Value::Nil,
+        &Value::Nil,
     ];
     let [grid_id, top, bottom, left, right, rows, columns] =
         extract_values(grid_scroll_arguments, values)?;
@@ -563,14 +575,14 @@ fn parse_grid_scroll(grid_scroll_arguments: Vec<Value>) -> Result<RedrawEvent> {
     })
 }
 
-fn parse_win_pos(win_pos_arguments: Vec<Value>) -> Result<RedrawEvent> {
+fn parse_win_pos(win_pos_arguments: &[Value]) -> Result<RedrawEvent> {
     let values = [
-        Value::Nil,
-        Value::Nil,
-        Value::Nil,
-        Value::Nil,
-        Value::Nil,
-        Value::Nil,
+        &Value::Nil,
+        &Value::Nil,
+        &Value::Nil,
+        &Value::Nil,
+        &Value::Nil,
+        &Value::Nil,
     ];
     let [grid, window, start_row, start_column, width, height] =
         extract_values(win_pos_arguments, values)?;
@@ -585,7 +597,7 @@ fn parse_win_pos(win_pos_arguments: Vec<Value>) -> Result<RedrawEvent> {
     })
 }
 
-fn parse_window_anchor(value: Value) -> Result<WindowAnchor> {
+fn parse_window_anchor(value: &Value) -> Result<WindowAnchor> {
     let value_str = parse_string(value)?;
     match value_str.as_str() {
         "NW" => Ok(WindowAnchor::NorthWest),
@@ -596,15 +608,15 @@ fn parse_window_anchor(value: Value) -> Result<WindowAnchor> {
     }
 }
 
-fn parse_win_float_pos(win_float_pos_arguments: Vec<Value>) -> Result<RedrawEvent> {
+fn parse_win_float_pos(win_float_pos_arguments: &[Value]) -> Result<RedrawEvent> {
     let values = [
-        Value::Nil,
-        Value::Nil,
-        Value::Nil,
-        Value::Nil,
-        Value::Nil,
-        Value::Nil,
-        Value::Nil,
+        &Value::Nil,
+        &Value::Nil,
+        &Value::Nil,
+        &Value::Nil,
+        &Value::Nil,
+        &Value::Nil,
+        &Value::Nil,
     ];
     let [grid, window, anchor, anchor_grid, anchor_row, anchor_column, focusable] =
         extract_values(win_float_pos_arguments, values)?;
@@ -620,8 +632,8 @@ fn parse_win_float_pos(win_float_pos_arguments: Vec<Value>) -> Result<RedrawEven
     })
 }
 
-fn parse_win_external_pos(win_external_pos_arguments: Vec<Value>) -> Result<RedrawEvent> {
-    let [grid, window] = extract_values(win_external_pos_arguments, [Value::Nil, Value::Nil])?;
+fn parse_win_external_pos(win_external_pos_arguments: &[Value]) -> Result<RedrawEvent> {
+    let [grid, window] = extract_values(win_external_pos_arguments, [&Value::Nil, &Value::Nil])?;
 
     Ok(RedrawEvent::WindowExternalPosition {
         grid: parse_u64(grid)?,
@@ -629,24 +641,24 @@ fn parse_win_external_pos(win_external_pos_arguments: Vec<Value>) -> Result<Redr
     })
 }
 
-fn parse_win_hide(win_hide_arguments: Vec<Value>) -> Result<RedrawEvent> {
-    let [grid] = extract_values(win_hide_arguments, [Value::Nil])?;
+fn parse_win_hide(win_hide_arguments: &[Value]) -> Result<RedrawEvent> {
+    let [grid] = extract_values(win_hide_arguments, [&Value::Nil])?;
 
     Ok(RedrawEvent::WindowHide {
         grid: parse_u64(grid)?,
     })
 }
 
-fn parse_win_close(win_close_arguments: Vec<Value>) -> Result<RedrawEvent> {
-    let [grid] = extract_values(win_close_arguments, [Value::Nil])?;
+fn parse_win_close(win_close_arguments: &[Value]) -> Result<RedrawEvent> {
+    let [grid] = extract_values(win_close_arguments, [&Value::Nil])?;
 
     Ok(RedrawEvent::WindowClose {
         grid: parse_u64(grid)?,
     })
 }
 
-fn parse_msg_set_pos(msg_set_pos_arguments: Vec<Value>) -> Result<RedrawEvent> {
-    let values = [Value::Nil, Value::Nil, Value::Nil, Value::Nil];
+fn parse_msg_set_pos(msg_set_pos_arguments: &[Value]) -> Result<RedrawEvent> {
+    let values = [&Value::Nil, &Value::Nil, &Value::Nil, &Value::Nil];
     let [grid, row, scrolled, separator_character] = extract_values(msg_set_pos_arguments, values)?;
 
     Ok(RedrawEvent::MessageSetPosition {
@@ -657,25 +669,25 @@ fn parse_msg_set_pos(msg_set_pos_arguments: Vec<Value>) -> Result<RedrawEvent> {
     })
 }
 
-fn parse_styled_content(line: Value) -> Result<StyledContent> {
+fn parse_styled_content(line: &Value) -> Result<StyledContent> {
     parse_array(line)?
-        .into_iter()
+        .iter()
         .map(|tuple| {
-            let [style_id, text] = extract_values(parse_array(tuple)?, [Value::Nil, Value::Nil])?;
+            let [style_id, text] = extract_values(parse_array(tuple)?, [&Value::Nil, &Value::Nil])?;
 
             Ok((parse_u64(style_id)?, parse_string(text)?))
         })
         .collect()
 }
 
-fn parse_cmdline_show(cmdline_show_arguments: Vec<Value>) -> Result<RedrawEvent> {
+fn parse_cmdline_show(cmdline_show_arguments: &[Value]) -> Result<RedrawEvent> {
     let values = [
-        Value::Nil,
-        Value::Nil,
-        Value::Nil,
-        Value::Nil,
-        Value::Nil,
-        Value::Nil,
+        &Value::Nil,
+        &Value::Nil,
+        &Value::Nil,
+        &Value::Nil,
+        &Value::Nil,
+        &Value::Nil,
     ];
     let [content, position, first_character, prompt, indent, level] =
         extract_values(cmdline_show_arguments, values)?;
@@ -690,8 +702,8 @@ fn parse_cmdline_show(cmdline_show_arguments: Vec<Value>) -> Result<RedrawEvent>
     })
 }
 
-fn parse_cmdline_pos(cmdline_pos_arguments: Vec<Value>) -> Result<RedrawEvent> {
-    let [position, level] = extract_values(cmdline_pos_arguments, [Value::Nil, Value::Nil])?;
+fn parse_cmdline_pos(cmdline_pos_arguments: &[Value]) -> Result<RedrawEvent> {
+    let [position, level] = extract_values(cmdline_pos_arguments, [&Value::Nil, &Value::Nil])?;
 
     Ok(RedrawEvent::CommandLinePosition {
         position: parse_u64(position)?,
@@ -699,10 +711,10 @@ fn parse_cmdline_pos(cmdline_pos_arguments: Vec<Value>) -> Result<RedrawEvent> {
     })
 }
 
-fn parse_cmdline_special_char(cmdline_special_char_arguments: Vec<Value>) -> Result<RedrawEvent> {
+fn parse_cmdline_special_char(cmdline_special_char_arguments: &[Value]) -> Result<RedrawEvent> {
     let [character, shift, level] = extract_values(
         cmdline_special_char_arguments,
-        [Value::Nil, Value::Nil, Value::Nil],
+        [&Value::Nil, &Value::Nil, &Value::Nil],
     )?;
 
     Ok(RedrawEvent::CommandLineSpecialCharacter {
@@ -712,28 +724,28 @@ fn parse_cmdline_special_char(cmdline_special_char_arguments: Vec<Value>) -> Res
     })
 }
 
-fn parse_cmdline_block_show(cmdline_block_show_arguments: Vec<Value>) -> Result<RedrawEvent> {
-    let [lines] = extract_values(cmdline_block_show_arguments, [Value::Nil])?;
+fn parse_cmdline_block_show(cmdline_block_show_arguments: &[Value]) -> Result<RedrawEvent> {
+    let [lines] = extract_values(cmdline_block_show_arguments, [&Value::Nil])?;
 
     Ok(RedrawEvent::CommandLineBlockShow {
         lines: parse_array(lines)?
-            .into_iter()
+            .iter()
             .map(parse_styled_content)
             .collect::<Result<_>>()?,
     })
 }
 
-fn parse_cmdline_block_append(cmdline_block_append_arguments: Vec<Value>) -> Result<RedrawEvent> {
-    let [line] = extract_values(cmdline_block_append_arguments, [Value::Nil])?;
+fn parse_cmdline_block_append(cmdline_block_append_arguments: &[Value]) -> Result<RedrawEvent> {
+    let [line] = extract_values(cmdline_block_append_arguments, [&Value::Nil])?;
 
     Ok(RedrawEvent::CommandLineBlockAppend {
         line: parse_styled_content(line)?,
     })
 }
 
-fn parse_msg_show(msg_show_arguments: Vec<Value>) -> Result<RedrawEvent> {
+fn parse_msg_show(msg_show_arguments: &[Value]) -> Result<RedrawEvent> {
     let [kind, content, replace_last] =
-        extract_values(msg_show_arguments, [Value::Nil, Value::Nil, Value::Nil])?;
+        extract_values(msg_show_arguments, [&Value::Nil, &Value::Nil, &Value::Nil])?;
 
     Ok(RedrawEvent::MessageShow {
         kind: MessageKind::parse(&parse_string(kind)?),
@@ -742,32 +754,32 @@ fn parse_msg_show(msg_show_arguments: Vec<Value>) -> Result<RedrawEvent> {
     })
 }
 
-fn parse_msg_showmode(msg_showmode_arguments: Vec<Value>) -> Result<RedrawEvent> {
-    let [content] = extract_values(msg_showmode_arguments, [Value::Nil])?;
+fn parse_msg_showmode(msg_showmode_arguments: &[Value]) -> Result<RedrawEvent> {
+    let [content] = extract_values(msg_showmode_arguments, [&Value::Nil])?;
 
     Ok(RedrawEvent::MessageShowMode {
         content: parse_styled_content(content)?,
     })
 }
 
-fn parse_msg_showcmd(msg_showcmd_arguments: Vec<Value>) -> Result<RedrawEvent> {
-    let [content] = extract_values(msg_showcmd_arguments, [Value::Nil])?;
+fn parse_msg_showcmd(msg_showcmd_arguments: &[Value]) -> Result<RedrawEvent> {
+    let [content] = extract_values(msg_showcmd_arguments, [&Value::Nil])?;
 
     Ok(RedrawEvent::MessageShowCommand {
         content: parse_styled_content(content)?,
     })
 }
 
-fn parse_msg_ruler(msg_ruler_arguments: Vec<Value>) -> Result<RedrawEvent> {
-    let [content] = extract_values(msg_ruler_arguments, [Value::Nil])?;
+fn parse_msg_ruler(msg_ruler_arguments: &[Value]) -> Result<RedrawEvent> {
+    let [content] = extract_values(msg_ruler_arguments, [&Value::Nil])?;
 
     Ok(RedrawEvent::MessageRuler {
         content: parse_styled_content(content)?,
     })
 }
 
-fn parse_msg_history_entry(entry: Value) -> Result<(MessageKind, StyledContent)> {
-    let [kind, content] = extract_values(parse_array(entry)?, [Value::Nil, Value::Nil])?;
+fn parse_msg_history_entry(entry: &Value) -> Result<(MessageKind, StyledContent)> {
+    let [kind, content] = extract_values(parse_array(entry)?, [&Value::Nil, &Value::Nil])?;
 
     Ok((
         MessageKind::parse(&parse_string(kind)?),
@@ -775,85 +787,115 @@ fn parse_msg_history_entry(entry: Value) -> Result<(MessageKind, StyledContent)>
     ))
 }
 
-fn parse_msg_history_show(msg_history_show_arguments: Vec<Value>) -> Result<RedrawEvent> {
-    let [entries] = extract_values(msg_history_show_arguments, [Value::Nil])?;
+fn parse_msg_history_show(msg_history_show_arguments: &[Value]) -> Result<RedrawEvent> {
+    let [entries] = extract_values(msg_history_show_arguments, [&Value::Nil])?;
 
     Ok(RedrawEvent::MessageHistoryShow {
         entries: parse_array(entries)?
-            .into_iter()
+            .iter()
             .map(parse_msg_history_entry)
             .collect::<Result<_>>()?,
     })
 }
 
-pub fn parse_redraw_event(event_value: Value) -> Result<Vec<RedrawEvent>> {
-    let mut event_contents = parse_array(event_value)?.into_iter();
-    let event_name = event_contents
-        .next()
+fn parse_event(event_name: &str, event_parameters: &[Value]) -> Result<Option<RedrawEvent>> {
+    Ok(match event_name {
+        "set_title" => Some(parse_set_title(event_parameters)?),
+        "set_icon" => None, // Ignore set icon for now
+        "mode_info_set" => Some(parse_mode_info_set(event_parameters)?),
+        "option_set" => Some(parse_option_set(event_parameters)?),
+        "mode_change" => Some(parse_mode_change(event_parameters)?),
+        "mouse_on" => Some(RedrawEvent::MouseOn),
+        "mouse_off" => Some(RedrawEvent::MouseOff),
+        "busy_start" => Some(RedrawEvent::BusyStart),
+        "busy_stop" => Some(RedrawEvent::BusyStop),
+        "flush" => Some(RedrawEvent::Flush),
+        "grid_resize" => Some(parse_grid_resize(event_parameters)?),
+        "default_colors_set" => Some(parse_default_colors(event_parameters)?),
+        "hl_attr_define" => Some(parse_hl_attr_define(event_parameters)?),
+        "grid_line" => Some(parse_grid_line(event_parameters)?),
+        "grid_clear" => Some(parse_clear(event_parameters)?),
+        "grid_cursor_goto" => Some(parse_cursor_goto(event_parameters)?),
+        "grid_scroll" => Some(parse_grid_scroll(event_parameters)?),
+        "win_pos" => Some(parse_win_pos(event_parameters)?),
+        "win_float_pos" => Some(parse_win_float_pos(event_parameters)?),
+        "win_external_pos" => Some(parse_win_external_pos(event_parameters)?),
+        "win_hide" => Some(parse_win_hide(event_parameters)?),
+        "win_close" => Some(parse_win_close(event_parameters)?),
+        "msg_set_pos" => Some(parse_msg_set_pos(event_parameters)?),
+        "cmdline_show" => Some(parse_cmdline_show(event_parameters)?),
+        "cmdline_pos" => Some(parse_cmdline_pos(event_parameters)?),
+        "cmdline_special_char" => Some(parse_cmdline_special_char(event_parameters)?),
+        "cmdline_hide" => Some(RedrawEvent::CommandLineHide),
+        "cmdline_block_show" => Some(parse_cmdline_block_show(event_parameters)?),
+        "cmdline_block_append" => Some(parse_cmdline_block_append(event_parameters)?),
+        "cmdline_block_hide" => Some(RedrawEvent::CommandLineBlockHide),
+        "msg_show" => Some(parse_msg_show(event_parameters)?),
+        "msg_clear" => Some(RedrawEvent::MessageClear),
+        "msg_showmode" => Some(parse_msg_showmode(event_parameters)?),
+        "msg_showcmd" => Some(parse_msg_showcmd(event_parameters)?),
+        "msg_ruler" => Some(parse_msg_ruler(event_parameters)?),
+        "msg_history_show" => Some(parse_msg_history_show(event_parameters)?),
+        _ => None,
+    })
+}
+
+fn parse_event_name(event_value: &Value) -> Result<String> {
+    parse_array(event_value)?
+        .first()
         .ok_or(EventParseError::InvalidEventFormat)
-        .and_then(parse_string)?;
-
-    let events = event_contents;
-    let mut parsed_events = Vec::with_capacity(events.len());
-
-    for event in events {
-        let event_parameters = parse_array(event)?;
-        let possible_parsed_event = match event_name.as_str() {
-            "set_title" => Some(parse_set_title(event_parameters)?),
-            "set_icon" => None, // Ignore set icon for now
-            "mode_info_set" => Some(parse_mode_info_set(event_parameters)?),
-            "option_set" => Some(parse_option_set(event_parameters)?),
-            "mode_change" => Some(parse_mode_change(event_parameters)?),
-            "mouse_on" => Some(RedrawEvent::MouseOn),
-            "mouse_off" => Some(RedrawEvent::MouseOff),
-            "busy_start" => Some(RedrawEvent::BusyStart),
-            "busy_stop" => Some(RedrawEvent::BusyStop),
-            "flush" => Some(RedrawEvent::Flush),
-            "grid_resize" => Some(parse_grid_resize(event_parameters)?),
-            "default_colors_set" => Some(parse_default_colors(event_parameters)?),
-            "hl_attr_define" => Some(parse_hl_attr_define(event_parameters)?),
-            "grid_line" => Some(parse_grid_line(event_parameters)?),
-            "grid_clear" => Some(parse_clear(event_parameters)?),
-            "grid_cursor_goto" => Some(parse_cursor_goto(event_parameters)?),
-            "grid_scroll" => Some(parse_grid_scroll(event_parameters)?),
-            "win_pos" => Some(parse_win_pos(event_parameters)?),
-            "win_float_pos" => Some(parse_win_float_pos(event_parameters)?),
-            "win_external_pos" => Some(parse_win_external_pos(event_parameters)?),
-            "win_hide" => Some(parse_win_hide(event_parameters)?),
-            "win_close" => Some(parse_win_close(event_parameters)?),
-            "msg_set_pos" => Some(parse_msg_set_pos(event_parameters)?),
-            "cmdline_show" => Some(parse_cmdline_show(event_parameters)?),
-            "cmdline_pos" => Some(parse_cmdline_pos(event_parameters)?),
-            "cmdline_special_char" => Some(parse_cmdline_special_char(event_parameters)?),
-            "cmdline_hide" => Some(RedrawEvent::CommandLineHide),
-            "cmdline_block_show" => Some(parse_cmdline_block_show(event_parameters)?),
-            "cmdline_block_append" => Some(parse_cmdline_block_append(event_parameters)?),
-            "cmdline_block_hide" => Some(RedrawEvent::CommandLineBlockHide),
-            "msg_show" => Some(parse_msg_show(event_parameters)?),
-            "msg_clear" => Some(RedrawEvent::MessageClear),
-            "msg_showmode" => Some(parse_msg_showmode(event_parameters)?),
-            "msg_showcmd" => Some(parse_msg_showcmd(event_parameters)?),
-            "msg_ruler" => Some(parse_msg_ruler(event_parameters)?),
-            "msg_history_show" => Some(parse_msg_history_show(event_parameters)?),
-            _ => None,
-        };
+        .and_then(parse_string)
+}
 
-        if let Some(parsed_event) = possible_parsed_event {
-            parsed_events.push(parsed_event);
-        }
-    }
+// Takes the events out of a batch whose name has already been parsed.
+fn batch_events(batch: Value) -> impl Iterator<Item = Value> {
+    let contents: Vec<Value> = batch.try_into().unwrap_or_default();
+    contents.into_iter().skip(1)
+}
 
-    Ok(parsed_events)
+fn parse_batch_event(event_name: &str, event_value: &Value) -> Result<Option<RedrawEvent>> {
+    parse_event(event_name, parse_array(event_value)?)
+}
+
+pub fn parse_redraw_event(event_value: Value) -> Result<Vec<RedrawEvent>> {
+    let event_name = parse_event_name(&event_value)?;
+    batch_events(event_value)
+        .filter_map(|event| parse_batch_event(&event_name, &event).transpose())
+        .collect()
 }
 
-pub(super) fn handle_redraw_event_group(arguments: Vec<Value>) {
-    for events in arguments {
-        let parsed_events = parse_redraw_event(events)
-            .unwrap_or_explained_panic("Could not parse event from neovim");
+// Applies each event separately so that a single malformed event (for instance one from a newer
+// nvim version) is logged and skipped instead of taking down the whole ui. Events are recorded
+// for crash reports before they are parsed, so a panic while parsing or drawing one still has it
+// in the report.
+pub(super) fn handle_redraw_event_group(arguments: Vec<Value>, editor: &Mutex<Editor>) {
+    for batch in arguments {
+        let event_name = match parse_event_name(&batch) {
+            Ok(event_name) => event_name,
+            Err(error) => {
+                report_recoverable_error(
+                    &format!("Could not parse redraw event batch {}", batch),
+                    error,
+                );
+                continue;
+            }
+        };
+
+        for event in batch_events(batch) {
+            let event = Arc::new(event);
+            CRASH_REPORTER.record_redraw_event(&event_name, event.clone());
 
-        for parsed_event in parsed_events {
-            let mut editor = EDITOR.lock();
-            editor.handle_redraw_event(parsed_event);
+            match parse_batch_event(&event_name, &event) {
+                Ok(Some(parsed_event)) => {
+                    let mut editor = editor.lock();
+                    editor.handle_redraw_event(parsed_event);
+                }
+                Ok(None) => {}
+                Err(error) => report_recoverable_error(
+                    &format!("Skipped {} event {}", event_name, event),
+                    error,
+                ),
+            }
         }
     }
 }
@@ -0,0 +1,84 @@
+use std::path::PathBuf;
+
+use rmpv::Value;
+use tokio::sync::oneshot;
+
+// Requests from the runtime api which have to be answered by the window, since only the ui thread
+// can touch the renderer and the sdl window.
+#[derive(Debug, Clone)]
+pub enum GuiRequest {
+    SetFont { name: String, size: Option<f32> },
+    Screenshot(PathBuf),
+    Zoom(f32),
+    ToggleFullscreen,
+    GetMetrics,
+    GetCellSize,
+    GetWindowSize,
+    GetScaleFactor,
+    GetFont,
+    GetRefreshRate,
+    PauseAnimations,
+    ResumeAnimations,
+    StepAnimations(f32),
+    SetParticleSeed(u64),
+}
+
+pub type GuiResponder = oneshot::Sender<Result<Value, String>>;
+
+fn string_argument(arguments: &[Value], index: usize) -> Result<String, String> {
+    arguments
+        .get(index)
+        .and_then(Value::as_str)
+        .map(String::from)
+        .ok_or_else(|| format!("Expected a string as argument {}", index + 1))
+}
+
+fn number_argument(arguments: &[Value], index: usize) -> Result<Option<f32>, String> {
+    match arguments.get(index) {
+        None | Some(Value::Nil) => Ok(None),
+        Some(value) => value
+            .as_f64()
+            .map(|number| Some(number as f32))
+            .ok_or_else(|| format!("Expected a number as argument {}", index + 1)),
+    }
+}
+
+fn integer_argument(arguments: &[Value], index: usize) -> Result<u64, String> {
+    arguments
+        .get(index)
+        .and_then(Value::as_u64)
+        .ok_or_else(|| format!("Expected a positive integer as argument {}", index + 1))
+}
+
+impl GuiRequest {
+    pub fn parse(method: &str, arguments: &[Value]) -> Result<GuiRequest, String> {
+        match method {
+            "neovide.set_font" => Ok(GuiRequest::SetFont {
+                name: string_argument(arguments, 0)?,
+                size: number_argument(arguments, 1)?,
+            }),
+            "neovide.screenshot" => Ok(GuiRequest::Screenshot(PathBuf::from(string_argument(
+                arguments, 0,
+            )?))),
+            "neovide.zoom" => number_argument(arguments, 0)?
+                .map(GuiRequest::Zoom)
+                .ok_or_else(|| String::from("Expected a zoom factor")),
+            "neovide.toggle_fullscreen" => Ok(GuiRequest::ToggleFullscreen),
+            "neovide.get_metrics" => Ok(GuiRequest::GetMetrics),
+            "neovide.get_cell_size" => Ok(GuiRequest::GetCellSize),
+            "neovide.get_window_size" => Ok(GuiRequest::GetWindowSize),
+            "neovide.get_scale_factor" => Ok(GuiRequest::GetScaleFactor),
+            "neovide.get_font" => Ok(GuiRequest::GetFont),
+            "neovide.get_refresh_rate" => Ok(GuiRequest::GetRefreshRate),
+            "neovide.pause_animations" => Ok(GuiRequest::PauseAnimations),
+            "neovide.resume_animations" => Ok(GuiRequest::ResumeAnimations),
+            "neovide.step_animations" => number_argument(arguments, 0)?
+                .map(GuiRequest::StepAnimations)
+                .ok_or_else(|| String::from("Expected a duration in seconds")),
+            "neovide.set_particle_seed" => {
+                Ok(GuiRequest::SetParticleSeed(integer_argument(arguments, 0)?))
+            }
+            _ => Err(format!("Unknown request {}", method)),
+        }
+    }
+}
@@ -1,37 +1,104 @@
+use std::sync::Arc;
+
 use async_trait::async_trait;
 use log::trace;
-use nvim_rs::{compat::tokio::Compat, Handler, Neovim};
+use nvim_rs::{Handler, Neovim};
 use rmpv::Value;
-use tokio::process::ChildStdin;
 use tokio::task;
 
+use super::connection::NeovimWriter;
 use super::events::handle_redraw_event_group;
-use crate::settings::SETTINGS;
+use super::gui_requests::GuiRequest;
+use super::image_requests::{handle_image_request, is_image_request};
+use super::ui_commands::UiCommand;
+use super::Bridge;
+use crate::remote;
+use crate::settings::{Settings, SETTINGS};
+use crate::window;
 
-#[derive(Clone)]
-pub struct NeovimHandler();
+#[derive(new, Clone)]
+pub struct NeovimHandler {
+    bridge: Arc<Bridge>,
+    session: u64,
+}
 
 #[async_trait]
 impl Handler for NeovimHandler {
-    type Writer = Compat<ChildStdin>;
+    type Writer = NeovimWriter;
 
     async fn handle_notify(
         &self,
         event_name: String,
         arguments: Vec<Value>,
-        _neovim: Neovim<Compat<ChildStdin>>,
+        neovim: Neovim<NeovimWriter>,
     ) {
         trace!("Neovim notification: {:?}", &event_name);
+        if !self.bridge.is_current_session(self.session) {
+            return;
+        }
+
+        // Rejected setting values are reported back to nvim, which needs the async api.
+        if Settings::is_settings_notification(&event_name) {
+            if !SETTINGS.is_owner(&self.bridge) {
+                return;
+            }
+            for error in SETTINGS.handle_changed_notification(&event_name, arguments) {
+                SETTINGS.reject_value(&neovim, error).await;
+            }
+            return;
+        }
+
+        let bridge = self.bridge.clone();
         task::spawn_blocking(move || match event_name.as_ref() {
             "redraw" => {
-                handle_redraw_event_group(arguments);
+                handle_redraw_event_group(arguments, &bridge.editor);
+            }
+            "neovide_restart" => {
+                bridge.queue_command(UiCommand::Restart);
+            }
+            "neovide_detach" => {
+                bridge.queue_command(UiCommand::Detach);
             }
-            "setting_changed" => {
-                SETTINGS.handle_changed_notification(arguments);
+            "neovide_new_window" => {
+                window::request_new_window();
+            }
+            "neovide_remote_done" => {
+                if let Some(wait_id) = arguments.get(0).and_then(Value::as_u64) {
+                    remote::buffer_closed(wait_id);
+                }
             }
             _ => {}
         })
         .await
         .ok();
     }
+
+    async fn handle_request(
+        &self,
+        method: String,
+        arguments: Vec<Value>,
+        _neovim: Neovim<NeovimWriter>,
+    ) -> Result<Value, Value> {
+        trace!("Neovim request: {:?}", &method);
+        if !self.bridge.is_current_session(self.session) {
+            return Err(Value::from("This Neovide session has ended"));
+        }
+
+        if method == "neovide.list_settings" {
+            return Ok(SETTINGS.describe());
+        }
+
+        if is_image_request(&method) {
+            let bridge = self.bridge.clone();
+            return task::spawn_blocking(move || {
+                handle_image_request(&method, &arguments, &bridge)
+            })
+            .await
+            .unwrap_or_else(|error| Err(error.to_string()))
+            .map_err(Value::from);
+        }
+
+        let request = GuiRequest::parse(&method, &arguments).map_err(Value::from)?;
+        self.bridge.gui_request(request).await.map_err(Value::from)
+    }
 }
@@ -0,0 +1,74 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use rmpv::Value;
+
+use super::Bridge;
+use crate::editor::InlineImage;
+
+// Image requests only touch the editor, so unlike gui requests they are answered directly from
+// the handler instead of waiting for the window.
+pub fn is_image_request(method: &str) -> bool {
+    method == "neovide.image_show" || method == "neovide.image_hide"
+}
+
+fn integer_argument(arguments: &[Value], index: usize) -> Result<u64, String> {
+    arguments
+        .get(index)
+        .and_then(Value::as_u64)
+        .ok_or_else(|| format!("Expected a positive integer as argument {}", index + 1))
+}
+
+// Strings naming an existing file are read from disk. Anything else, including msgpack binary
+// and lua strings holding raw bytes, is decoded as image data.
+fn decode_image(source: &Value) -> Result<image::RgbaImage, String> {
+    let image = match source {
+        Value::String(text) => match text.as_str().map(Path::new) {
+            Some(path) if path.is_file() => image::open(path),
+            _ => image::load_from_memory(text.as_bytes()),
+        },
+        Value::Binary(data) => image::load_from_memory(data),
+        _ => return Err(String::from("Expected a path or image data as argument 2")),
+    };
+
+    image
+        .map(|image| image.to_rgba())
+        .map_err(|error| format!("Could not decode image: {}", error))
+}
+
+fn show_image(arguments: &[Value], bridge: &Bridge) -> Result<Value, String> {
+    let id = integer_argument(arguments, 0)?;
+    let pixels = decode_image(arguments.get(1).unwrap_or(&Value::Nil))?;
+    let image = InlineImage::new(
+        integer_argument(arguments, 2)?,
+        integer_argument(arguments, 3)? as i64,
+        integer_argument(arguments, 4)?,
+        integer_argument(arguments, 5)?,
+        integer_argument(arguments, 6)?,
+        Arc::new(pixels),
+    );
+
+    bridge.editor.lock().images.insert(id, image);
+    bridge.redraw_scheduler.queue_next_frame();
+    Ok(Value::Nil)
+}
+
+fn hide_image(arguments: &[Value], bridge: &Bridge) -> Result<Value, String> {
+    let id = integer_argument(arguments, 0)?;
+    if bridge.editor.lock().images.remove(&id).is_some() {
+        bridge.redraw_scheduler.queue_next_frame();
+    }
+    Ok(Value::Nil)
+}
+
+pub fn handle_image_request(
+    method: &str,
+    arguments: &[Value],
+    bridge: &Bridge,
+) -> Result<Value, String> {
+    match method {
+        "neovide.image_show" => show_image(arguments, bridge),
+        "neovide.image_hide" => hide_image(arguments, bridge),
+        _ => Err(format!("Unknown request {}", method)),
+    }
+}
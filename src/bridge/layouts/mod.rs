@@ -1,9 +1,9 @@
 mod qwerty;
 
-use log::{error, trace};
+use log::trace;
 use skulpin::sdl2::keyboard::{Keycode, Mod};
 
-use crate::settings::{FromValue, Value, SETTINGS};
+use crate::settings::{SettingEnum, Value, SETTINGS};
 
 use qwerty::*;
 
@@ -12,31 +12,11 @@ pub fn unsupported_key<R>(keycode: Keycode) -> Option<R> {
     None
 }
 
-#[derive(Clone)]
+#[derive(Clone, SettingEnum)]
 pub enum KeyboardLayout {
     Qwerty,
 }
 
-impl FromValue for KeyboardLayout {
-    fn from_value(&mut self, value: Value) {
-        match value.as_str() {
-            Some("qwerty") => *self = KeyboardLayout::Qwerty,
-            _ => error!(
-                "keyboard_layout setting expected a known keyboard layout name, but received: {}",
-                value
-            ),
-        }
-    }
-}
-
-impl From<KeyboardLayout> for Value {
-    fn from(layout: KeyboardLayout) -> Self {
-        match layout {
-            KeyboardLayout::Qwerty => "qwerty".into(),
-        }
-    }
-}
-
 #[derive(Clone)]
 struct KeyboardSettings {
     layout: KeyboardLayout,
@@ -1,31 +1,46 @@
 #[macro_use]
 pub mod layouts;
 
-mod events;
+mod connection;
+pub mod events;
+mod gui_requests;
 mod handler;
+mod image_requests;
+mod runtime_plugin;
 mod ui_commands;
 
 use std::process::Stdio;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
 
 use log::{error, info, trace};
-use nvim_rs::{create::tokio as create, UiAttachOptions};
+use nvim_rs::{Neovim, UiAttachOptions};
+use parking_lot::Mutex;
 use rmpv::Value;
-use tokio::process::Command;
+use tokio::io::{AsyncReadExt, BufReader};
+use tokio::process::{ChildStderr, Command};
 use tokio::runtime::Runtime;
 use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::sync::oneshot;
 
-use crate::error_handling::ResultPanicExplanation;
+use crate::command_line;
+use crate::crash_report::CRASH_REPORTER;
+use crate::editor::Editor;
+use crate::error_handling::report_recoverable_error;
+use crate::redraw_scheduler::RedrawScheduler;
 use crate::settings::*;
 use crate::window::window_geometry_or_default;
+pub use connection::NeovimWriter;
+use connection::*;
 pub use events::*;
+pub use gui_requests::{GuiRequest, GuiResponder};
 use handler::NeovimHandler;
 pub use layouts::*;
 pub use ui_commands::UiCommand;
 
 lazy_static! {
-    pub static ref BRIDGE: Bridge = Bridge::new();
+    // Shared by the bridges of every window.
+    static ref RUNTIME: Runtime = Runtime::new().unwrap();
 }
 
 #[cfg(target_os = "windows")]
@@ -35,10 +50,7 @@ fn set_windows_creation_flags(cmd: &mut Command) {
 
 #[cfg(target_os = "windows")]
 fn platform_build_nvim_cmd(bin: &str) -> Command {
-    if std::env::args()
-        .collect::<Vec<String>>()
-        .contains(&String::from("--wsl"))
-    {
+    if command_line::has_argument("--wsl") {
         let mut cmd = Command::new("wsl");
         cmd.arg(bin);
         cmd
@@ -72,12 +84,23 @@ fn build_nvim_cmd() -> Command {
     }
 }
 
-fn create_nvim_command() -> Command {
+// g:neovide marks nvim as running inside Neovide, unless the user already holds nested settings in
+// it.
+const MARK_NEOVIDE: &str =
+    "if type(get(g:, 'neovide')) != v:t_dict | let g:neovide = v:true | endif";
+
+// A headless nvim sources the user's config right away instead of waiting for a ui to attach, so
+// g:neovide has to be set from the command line for configs which check for it.
+fn create_nvim_command(arguments: &[String], startup_command: &str) -> Command {
     let mut cmd = build_nvim_cmd();
 
-    cmd.arg("--embed")
-        .args(SETTINGS.neovim_arguments.iter().skip(1))
-        .stderr(Stdio::inherit());
+    cmd.arg("--headless")
+        .arg("--cmd")
+        .arg(MARK_NEOVIDE)
+        .arg("--cmd")
+        .arg(startup_command)
+        .args(arguments)
+        .stderr(Stdio::piped());
 
     #[cfg(target_os = "windows")]
     set_windows_creation_flags(&mut cmd);
@@ -97,14 +120,74 @@ async fn drain(receiver: &mut UnboundedReceiver<UiCommand>) -> Option<Vec<UiComm
     }
 }
 
-async fn start_process(mut receiver: UnboundedReceiver<UiCommand>) {
-    let (width, height) = window_geometry_or_default();
-    let (mut nvim, io_handler, _) =
-        create::new_child_cmd(&mut create_nvim_command(), NeovimHandler())
+// Collects everything nvim writes to stderr while still passing it through to our own stderr.
+async fn capture_stderr(mut stderr: BufReader<ChildStderr>) -> String {
+    let mut captured = Vec::new();
+    let mut buffer = [0u8; 1024];
+
+    while let Ok(read) = stderr.read(&mut buffer).await {
+        if read == 0 {
+            break;
+        }
+        eprint!("{}", String::from_utf8_lossy(&buffer[..read]));
+        captured.extend_from_slice(&buffer[..read]);
+    }
+
+    String::from_utf8_lossy(&captured).into_owned()
+}
+
+fn neovim_arguments() -> Vec<String> {
+    command_line::neovim_arguments()
+        .into_iter()
+        .skip(1)
+        .collect()
+}
+
+// Starts an nvim process, or attaches to an already running one when given an address.
+async fn start_process(
+    bridge: Arc<Bridge>,
+    mut receiver: UnboundedReceiver<UiCommand>,
+    session: u64,
+    (width, height): (u64, u64),
+    attach_address: Option<String>,
+) {
+    let handler = NeovimHandler::new(bridge.clone(), session);
+    let connection = match &attach_address {
+        Some(address) => connect_to_address(address, handler)
+            .await
+            .map(|(nvim, io_handler)| (nvim, io_handler, None)),
+        None => start_child(handler, &bridge.arguments)
             .await
-            .unwrap_or_explained_panic("Could not locate or start the neovim process");
+            .map(|(nvim, io_handler, child, stderr)| (nvim, io_handler, Some((child, stderr)))),
+    };
+
+    let (mut nvim, io_handler, child) = match (connection, attach_address) {
+        (Ok(connection), _) => connection,
+        (Err(error), Some(address)) => {
+            bridge.fail(format!(
+                "Could not attach to the neovim server at {}: {}",
+                address, error
+            ));
+            return;
+        }
+        (Err(error), None) => {
+            bridge.fail(format!(
+                "Could not locate or start the neovim process: {}",
+                error
+            ));
+            return;
+        }
+    };
 
+    let started_child = child.is_some();
+    let (child, stderr_reader) = match child {
+        Some((child, stderr)) => (Some(child), Some(tokio::spawn(capture_stderr(stderr)))),
+        None => (None, None),
+    };
+
+    let close_bridge = bridge.clone();
     tokio::spawn(async move {
+        let bridge = close_bridge;
         info!("Close watcher started");
         match io_handler.await {
             Err(join_error) => error!("Error joining IO loop: '{}'", join_error),
@@ -115,22 +198,64 @@ async fn start_process(mut receiver: UnboundedReceiver<UiCommand>) {
             }
             Ok(Ok(())) => {}
         };
-        BRIDGE.running.store(false, Ordering::Relaxed);
-    });
 
-    if let Ok(Value::Integer(correct_version)) = nvim.eval("has(\"nvim-0.4\")").await {
-        if correct_version.as_i64() != Some(1) {
-            error!("Neovide requires version 0.4 or higher");
-            std::process::exit(0);
+        let status = match child {
+            Some(child) => Some(child.await),
+            None => None,
+        };
+        let stderr_output = match stderr_reader {
+            Some(reader) => reader.await.unwrap_or_default(),
+            None => String::new(),
+        };
+
+        if !bridge.is_current_session(session) {
+            info!("Neovim process from a previous session closed");
+            return;
         }
-    } else {
-        error!("Neovide requires version 0.4 or higher");
-        std::process::exit(0);
+
+        match status {
+            Some(Ok(status)) => {
+                info!("Neovim process exited with {}", status);
+                bridge.set_exit(NvimExit::from_status(status.code(), stderr_output));
+            }
+            Some(Err(error)) => bridge.set_exit(NvimExit {
+                code: 1,
+                error: Some(format!("Could not wait for the neovim process: {}", error)),
+            }),
+            None => info!("Disconnected from the neovim server"),
+        }
+        bridge.running.store(false, Ordering::Relaxed);
+    });
+
+    // Nvim started by Neovide needs stdioopen to quit along with us, which came with 0.5.
+    let required_version = if started_child { "0.5" } else { "0.4" };
+    let version_check = format!("has(\"nvim-{}\")", required_version);
+    let correct_version = match nvim.eval(&version_check).await {
+        Ok(Value::Integer(correct_version)) => correct_version.as_i64() == Some(1),
+        _ => false,
     };
+    if !correct_version {
+        if started_child {
+            // Otherwise the headless nvim would be left running
+            nvim.command("qall!").await.ok();
+        }
+        bridge.fail(format!(
+            "Neovide requires version {} or higher",
+            required_version
+        ));
+        return;
+    }
 
-    nvim.set_var("neovide", Value::Boolean(true))
-        .await
-        .unwrap_or_explained_panic("Could not communicate with neovim process");
+    // Nvim started by Neovide was already marked on its command line.
+    if !started_child {
+        if let Err(error) = nvim.command(MARK_NEOVIDE).await {
+            bridge.fail(format!(
+                "Could not communicate with neovim process: {}",
+                error
+            ));
+            return;
+        }
+    }
     let mut options = UiAttachOptions::new();
     options.set_linegrid_external(true);
     options.set_rgb(true);
@@ -142,17 +267,71 @@ async fn start_process(mut receiver: UnboundedReceiver<UiCommand>) {
         .await
         .ok();
     }
-    nvim.ui_attach(width as i64, height as i64, &options)
-        .await
-        .unwrap_or_explained_panic("Could not attach ui to neovim process");
+    if let Err(error) = nvim.ui_attach(width as i64, height as i64, &options).await {
+        bridge.fail(format!("Could not attach ui to neovim process: {}", error));
+        return;
+    }
     info!("Neovim process attached");
 
+    // Notifications have to be addressed to our own channel, which depends on how many clients
+    // connected before us.
+    let channel = match nvim.get_api_info().await {
+        Ok(api_info) => api_info.get(0).and_then(Value::as_u64).unwrap_or(1),
+        Err(error) => {
+            report_recoverable_error("Could not read the rpc channel id", error);
+            1
+        }
+    };
+    bridge.channel.store(channel, Ordering::Relaxed);
+
+    // The runtime api finds its way back to this window through the channel variable
+    if let Err(error) = nvim.set_var("neovide_channel", Value::from(channel)).await {
+        report_recoverable_error("Could not set g:neovide_channel", error);
+    }
+    runtime_plugin::add_to_runtimepath(&nvim).await;
+
+    // Nvim started by Neovide is a server of its own, so it has to be told to quit along with us
+    if started_child {
+        if let Err(error) = nvim.command("call neovide#quit_with_neovide()").await {
+            report_recoverable_error("Could not tie neovim to this process", error);
+        }
+    }
+
+    let commands = [
+        ("NeovideRestart", "neovide_restart"),
+        ("NeovideDetach", "neovide_detach"),
+        ("NeovideNewWindow", "neovide_new_window"),
+    ];
+    for (command_name, notification) in commands.iter() {
+        let definition = format!(
+            "command! {} call rpcnotify({}, '{}')",
+            command_name, channel, notification
+        );
+        if let Err(error) = nvim.command(&definition).await {
+            report_recoverable_error(
+                &format!("Could not define the {} command", command_name),
+                error,
+            );
+        }
+    }
+
+    // Listing settings only needs the runtime plugin, so it doesn't go through a notification.
+    let settings_command = concat!(
+        "command! -nargs=? -complete=customlist,neovide#complete_settings ",
+        "NeovideSettings call neovide#print_settings(<q-args>)"
+    );
+    if let Err(error) = nvim.command(settings_command).await {
+        report_recoverable_error("Could not define the NeovideSettings command", error);
+    }
+
     let nvim = Arc::new(nvim);
     let input_nvim = nvim.clone();
+    let input_bridge = bridge.clone();
     tokio::spawn(async move {
+        let bridge = input_bridge;
         info!("UiCommand processor started");
         while let Some(commands) = drain(&mut receiver).await {
-            if !BRIDGE.running.load(Ordering::Relaxed) {
+            if !bridge.running.load(Ordering::Relaxed) {
                 return;
             }
             let (resize_list, other_commands): (Vec<UiCommand>, Vec<UiCommand>) = commands
@@ -166,53 +345,248 @@ async fn start_process(mut receiver: UnboundedReceiver<UiCommand>) {
                 .chain(other_commands.into_iter())
             {
                 let input_nvim = input_nvim.clone();
+                let bridge = bridge.clone();
                 tokio::spawn(async move {
-                    if !BRIDGE.running.load(Ordering::Relaxed) {
+                    if !bridge.running.load(Ordering::Relaxed) {
                         return;
                     }
                     trace!("Executing UiCommand: {:?}", &command);
-                    command.execute(&input_nvim).await;
+                    let description = format!("{:?}", &command);
+                    if let Err(error) = command.execute(&input_nvim, &bridge).await {
+                        report_recoverable_error(
+                            &format!("Could not execute UiCommand {}", description),
+                            error,
+                        );
+                    }
                 });
             }
         }
     });
 
-    SETTINGS.read_initial_values(&nvim).await;
-    SETTINGS.setup_changed_listeners(&nvim).await;
+    watch_settings(&nvim, &bridge).await;
 
     nvim.set_option("lazyredraw", Value::Boolean(false))
         .await
         .ok();
 }
 
+// Reads the settings from the bridge's nvim and watches them for changes, as long as no other
+// window owns the settings.
+async fn watch_settings(nvim: &Neovim<NeovimWriter>, bridge: &Arc<Bridge>) {
+    if SETTINGS.claim(bridge) {
+        SETTINGS.read_initial_values(nvim).await;
+        SETTINGS
+            .setup_changed_listeners(nvim, bridge.channel())
+            .await;
+    }
+}
+
+// How the nvim process Neovide started ended. The error is set when the exit was unexpected and
+// should be shown to the user before Neovide closes.
+#[derive(Debug, Clone)]
+pub struct NvimExit {
+    pub code: i32,
+    pub error: Option<String>,
+}
+
+impl NvimExit {
+    fn from_status(code: Option<i32>, stderr_output: String) -> NvimExit {
+        let stderr_output = stderr_output.trim();
+
+        match code {
+            Some(0) => NvimExit {
+                code: 0,
+                error: None,
+            },
+            // Exiting with an error code and nothing to say is how :cq works, so only treat the
+            // exit as a failure if nvim complained about something.
+            Some(code) if stderr_output.is_empty() => NvimExit { code, error: None },
+            Some(code) => NvimExit {
+                code,
+                error: Some(format!(
+                    "Neovim exited with code {}:\n\n{}",
+                    code, stderr_output
+                )),
+            },
+            None => NvimExit {
+                code: 1,
+                error: Some(format!(
+                    "Neovim was terminated by a signal.\n\n{}",
+                    stderr_output
+                )),
+            },
+        }
+    }
+}
+
+// Every window has its own bridge, editor and redraw scheduler. The bridge lives as long as its
+// window, but the nvim process behind it can be swapped out. Each nvim process gets a new session
+// number so that anything still arriving from a previous process can be recognized and ignored.
 pub struct Bridge {
-    _runtime: Runtime, // Necessary to keep runtime running
-    sender: UnboundedSender<UiCommand>,
+    pub editor: Arc<Mutex<Editor>>,
+    pub redraw_scheduler: Arc<RedrawScheduler>,
+    arguments: Vec<String>,
+    sender: Mutex<UnboundedSender<UiCommand>>,
+    session: AtomicU64,
+    channel: AtomicU64,
     pub running: AtomicBool,
+    detached: AtomicBool,
+    exit: Mutex<Option<NvimExit>>,
+    gui_requests: Mutex<Vec<(GuiRequest, GuiResponder)>>,
 }
 
 impl Bridge {
-    pub fn new() -> Bridge {
-        let runtime = Runtime::new().unwrap();
+    // Starts nvim for a new window. New processes are started with the given arguments,
+    // unless an address to attach to is given.
+    pub fn start(attach_address: Option<String>, arguments: Vec<String>) -> Arc<Bridge> {
+        let redraw_scheduler = RedrawScheduler::new();
+        let dimensions = window_geometry_or_default();
+        let editor = Arc::new(Mutex::new(Editor::new(
+            redraw_scheduler.clone(),
+            dimensions,
+        )));
         let (sender, receiver) = unbounded_channel::<UiCommand>();
 
-        runtime.spawn(async move {
-            start_process(receiver).await;
-        });
-        Bridge {
-            _runtime: runtime,
-            sender,
+        let bridge = Arc::new(Bridge {
+            editor,
+            redraw_scheduler,
+            arguments,
+            sender: Mutex::new(sender),
+            session: AtomicU64::new(0),
+            channel: AtomicU64::new(1),
             running: AtomicBool::new(true),
+            detached: AtomicBool::new(false),
+            exit: Mutex::new(None),
+            gui_requests: Mutex::new(Vec::new()),
+        });
+
+        let process_bridge = bridge.clone();
+        RUNTIME.spawn(async move {
+            start_process(process_bridge, receiver, 0, dimensions, attach_address).await;
+        });
+
+        bridge
+    }
+
+    // The bridge for the first window. It attaches if asked to.
+    pub fn start_initial() -> Arc<Bridge> {
+        Bridge::start(attach_address(), neovim_arguments())
+    }
+
+    // Windows opened later always start their own nvim, with the same arguments as the first.
+    pub fn start_new_window() -> Arc<Bridge> {
+        Bridge::start(None, neovim_arguments())
+    }
+
+    pub fn is_detached(&self) -> bool {
+        self.detached.load(Ordering::Relaxed)
+    }
+
+    // Called once the ui has been detached from nvim. Nvim keeps running on its own and the
+    // window closes.
+    fn detach(&self, address: String) {
+        info!("Detached from neovim, still listening on {}", address);
+        remember_detached_session(&address);
+        println!(
+            "Neovim is still running. Reattach with: neovide --attach {}",
+            address
+        );
+        self.detached.store(true, Ordering::Relaxed);
+    }
+
+    // The rpc channel nvim knows this ui by. Notifications defined on the nvim side have to be
+    // addressed to it.
+    pub fn channel(&self) -> u64 {
+        self.channel.load(Ordering::Relaxed)
+    }
+
+    pub fn is_current_session(&self, session: u64) -> bool {
+        self.session.load(Ordering::Relaxed) == session
+    }
+
+    // Quits the current nvim process and starts a fresh one with the same arguments. The window,
+    // renderer and font caches stay as they are, only the editor state is reset. Unsaved changes
+    // are lost, so restarts go through UiCommand::Restart which checks for them first.
+    pub fn restart(bridge: &Arc<Bridge>) {
+        info!("Restarting neovim process");
+        let grid_size = {
+            let mut editor = bridge.editor.lock();
+            let grid_size = (editor.grid.width, editor.grid.height);
+            *editor = Editor::new(bridge.redraw_scheduler.clone(), grid_size);
+            grid_size
+        };
+
+        let (sender, receiver) = unbounded_channel::<UiCommand>();
+        let previous_sender = std::mem::replace(&mut *bridge.sender.lock(), sender);
+        let session = bridge.session.fetch_add(1, Ordering::Relaxed) + 1;
+        previous_sender.send(UiCommand::Quit).ok();
+
+        *bridge.exit.lock() = None;
+        bridge.running.store(true, Ordering::Relaxed);
+        let process_bridge = bridge.clone();
+        RUNTIME.spawn(async move {
+            start_process(process_bridge, receiver, session, grid_size, None).await;
+        });
+        bridge.redraw_scheduler.queue_next_frame();
+    }
+
+    fn set_exit(&self, exit: NvimExit) {
+        let mut current_exit = self.exit.lock();
+
+        // Keep the first failure. It is usually the cause of anything reported after it.
+        if current_exit
+            .as_ref()
+            .map(|current| current.error.is_none())
+            .unwrap_or(true)
+        {
+            *current_exit = Some(exit);
         }
     }
 
+    // Stops the bridge because nvim could not be started or attached to.
+    fn fail(&self, message: String) {
+        error!("{}", message);
+        self.set_exit(NvimExit {
+            code: 1,
+            error: Some(message),
+        });
+        self.running.store(false, Ordering::Relaxed);
+    }
+
+    pub fn exit_status(&self) -> NvimExit {
+        self.exit.lock().clone().unwrap_or(NvimExit {
+            code: 0,
+            error: None,
+        })
+    }
+
+    // Hands a request to the window and waits for its answer. The window picks requests up once
+    // per frame.
+    pub async fn gui_request(&self, request: GuiRequest) -> Result<Value, String> {
+        let (responder, response) = oneshot::channel();
+        self.gui_requests.lock().push((request, responder));
+        self.redraw_scheduler.queue_next_frame();
+
+        response
+            .await
+            .unwrap_or_else(|_| Err(String::from("The window closed before answering")))
+    }
+
+    pub fn take_gui_requests(&self) -> Vec<(GuiRequest, GuiResponder)> {
+        std::mem::take(&mut *self.gui_requests.lock())
+    }
+
     pub fn queue_command(&self, command: UiCommand) {
-        if !BRIDGE.running.load(Ordering::Relaxed) {
+        if !self.running.load(Ordering::Relaxed) {
             return;
         }
         trace!("UiCommand queued: {:?}", &command);
-        self.sender.send(command).unwrap_or_explained_panic(
-            "Could not send UI command from the window system to the neovim process.",
-        );
+        CRASH_REPORTER.record_ui_command(&command);
+        if let Err(error) = self.sender.lock().send(command) {
+            report_recoverable_error(
+                "Could not send UI command from the window system to the neovim process",
+                error,
+            );
+        }
     }
 }
@@ -0,0 +1,74 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use nvim_rs::Neovim;
+use rmpv::Value;
+
+use super::connection::NeovimWriter;
+use crate::error_handling::report_recoverable_error;
+use crate::paths::{data_directory, private_directory};
+
+// The runtime plugin providing require('neovide') and the neovide# autoload functions.
+#[derive(RustEmbed)]
+#[folder = "runtime/"]
+struct Asset;
+
+// Writes the embedded runtime files out so that nvim can find them. The directory is versioned so
+// that instances of different versions don't overwrite each other's files. Nvim runs whatever it
+// finds there, so the directory has to belong to the current user and be closed to everyone else,
+// which private_directory makes sure of.
+fn extract_runtime() -> io::Result<PathBuf> {
+    let runtime_directory = data_directory()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Could not find a data directory"))?
+        .join("runtime")
+        .join(env!("CARGO_PKG_VERSION"));
+    let directory = private_directory(&runtime_directory)?;
+
+    for file_name in Asset::iter() {
+        if let Some(contents) = Asset::get(&file_name) {
+            let path = directory.join(file_name.as_ref());
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(path, contents)?;
+        }
+    }
+
+    Ok(directory)
+}
+
+pub async fn add_to_runtimepath(nvim: &Neovim<NeovimWriter>) {
+    let directory = match extract_runtime() {
+        Ok(directory) => directory,
+        Err(error) => {
+            report_recoverable_error("Could not write the neovide runtime files", error);
+            return;
+        }
+    };
+
+    let runtimepath = match nvim.get_option("runtimepath").await {
+        Ok(runtimepath) => runtimepath.as_str().unwrap_or_default().to_string(),
+        Err(error) => {
+            report_recoverable_error("Could not read the runtimepath", error);
+            return;
+        }
+    };
+
+    // Commas separate runtimepath entries, so any in the path itself have to be escaped
+    let directory = directory.to_string_lossy().replace(',', "\\,");
+    if runtimepath.split(',').any(|entry| entry == directory) {
+        return;
+    }
+
+    let runtimepath = format!("{},{}", runtimepath, directory);
+    if let Err(error) = nvim
+        .set_option("runtimepath", Value::from(runtimepath))
+        .await
+    {
+        report_recoverable_error(
+            "Could not add the neovide runtime to the runtimepath",
+            error,
+        );
+    }
+}
@@ -1,9 +1,13 @@
-use log::trace;
-use nvim_rs::compat::tokio::Compat;
+use std::sync::Arc;
+
+use log::{info, trace};
+use nvim_rs::error::CallError;
 use nvim_rs::Neovim;
-use tokio::process::ChildStdin;
+use rmpv::Value;
 
-use crate::editor::EDITOR;
+use super::connection::{server_address, NeovimWriter};
+use super::{watch_settings, Bridge};
+use crate::remote;
 
 #[derive(Debug, Clone)]
 pub enum UiCommand {
@@ -22,64 +26,116 @@ pub enum UiCommand {
     },
     Drag(u32, u32),
     FileDrop(String),
+    OpenFiles {
+        files: Vec<String>,
+        in_tabs: bool,
+        wait_id: Option<u64>,
+    },
     FocusLost,
     FocusGained,
+    SetVariable {
+        name: String,
+        value: Value,
+    },
+    ShowError(String),
+    WatchSettings,
+    Restart,
+    Detach,
     Quit,
 }
 
 impl UiCommand {
-    pub async fn execute(self, nvim: &Neovim<Compat<ChildStdin>>) {
+    pub async fn execute(
+        self,
+        nvim: &Neovim<NeovimWriter>,
+        bridge: &Arc<Bridge>,
+    ) -> Result<(), Box<CallError>> {
         match self {
-            UiCommand::Resize { width, height } => nvim
-                .ui_try_resize(width.max(10) as i64, height.max(3) as i64)
-                .await
-                .expect("Resize failed"),
+            UiCommand::Resize { width, height } => {
+                nvim.ui_try_resize(width.max(10) as i64, height.max(3) as i64)
+                    .await?
+            }
             UiCommand::Keyboard(input_command) => {
                 trace!("Keyboard Input Sent: {}", input_command);
-                nvim.input(&input_command).await.expect("Input failed");
+                nvim.input(&input_command).await?;
             }
             UiCommand::MouseButton {
                 action,
                 position: (grid_x, grid_y),
             } => {
-                if { EDITOR.lock().mouse_enabled } {
+                if { bridge.editor.lock().mouse_enabled } {
                     nvim.input_mouse("left", &action, "", 0, grid_y as i64, grid_x as i64)
-                        .await
-                        .expect("Mouse Input Failed");
+                        .await?;
                 }
             }
             UiCommand::Scroll {
                 direction,
                 position: (grid_x, grid_y),
             } => {
-                if { EDITOR.lock().mouse_enabled } {
+                if { bridge.editor.lock().mouse_enabled } {
                     nvim.input_mouse("wheel", &direction, "", 0, grid_y as i64, grid_x as i64)
-                        .await
-                        .expect("Mouse Scroll Failed");
+                        .await?;
                 }
             }
             UiCommand::Drag(grid_x, grid_y) => {
-                if { EDITOR.lock().mouse_enabled } {
+                if { bridge.editor.lock().mouse_enabled } {
                     nvim.input_mouse("left", "drag", "", 0, grid_y as i64, grid_x as i64)
-                        .await
-                        .expect("Mouse Drag Failed");
+                        .await?;
                 }
             }
-            UiCommand::FocusLost => nvim
-                .command("if exists('#FocusLost') | doautocmd <nomodeline> FocusLost | endif")
-                .await
-                .expect("Focus Lost Failed"),
-            UiCommand::FocusGained => nvim
-                .command("if exists('#FocusGained') | doautocmd <nomodeline> FocusGained | endif")
-                .await
-                .expect("Focus Gained Failed"),
+            UiCommand::FocusLost => {
+                nvim.command("if exists('#FocusLost') | doautocmd <nomodeline> FocusLost | endif")
+                    .await?
+            }
+            UiCommand::FocusGained => {
+                nvim.command(
+                    "if exists('#FocusGained') | doautocmd <nomodeline> FocusGained | endif",
+                )
+                .await?
+            }
+            UiCommand::SetVariable { name, value } => nvim.set_var(&name, value).await?,
+            UiCommand::ShowError(message) => nvim.err_writeln(&message).await?,
+            UiCommand::WatchSettings => watch_settings(nvim, bridge).await,
+            UiCommand::Restart => {
+                // Restarting quits nvim with qa!, so anything unsaved would be lost
+                let modified_buffers = modified_buffers(nvim).await?;
+                if modified_buffers.is_empty() {
+                    Bridge::restart(bridge);
+                } else {
+                    info!("Not restarting, unsaved buffers: {:?}", modified_buffers);
+                    nvim.err_writeln(&format!(
+                        "Neovide can't restart while buffers have unsaved changes: {}",
+                        modified_buffers.join(", ")
+                    ))
+                    .await?;
+                }
+            }
+            UiCommand::Detach => {
+                let address = server_address(nvim).await?;
+                nvim.command("call neovide#detach()").await?;
+                nvim.ui_detach().await?;
+                bridge.detach(address);
+            }
             UiCommand::Quit => {
                 nvim.command("qa!").await.ok(); // Ignoring result as it won't succeed since the app closed.
             }
             UiCommand::FileDrop(path) => {
                 nvim.command(format!("e {}", path).as_str()).await.ok();
             }
+            UiCommand::OpenFiles {
+                files,
+                in_tabs,
+                wait_id,
+            } => {
+                let result = open_files(nvim, bridge, files, in_tabs, wait_id).await;
+                if let (Err(error), Some(wait_id)) = (&result, wait_id) {
+                    remote::open_failed(wait_id, &error.to_string());
+                }
+                result?
+            }
         }
+
+        Ok(())
     }
 
     pub fn is_resize(&self) -> bool {
@@ -89,3 +145,68 @@ impl UiCommand {
         }
     }
 }
+
+// Names of the buffers with changes that haven't been written yet. Unnamed buffers are listed by
+// number.
+async fn modified_buffers(nvim: &Neovim<NeovimWriter>) -> Result<Vec<String>, Box<CallError>> {
+    let filter = Value::Map(vec![(Value::from("bufmodified"), Value::from(1))]);
+    let buffers = nvim.call_function("getbufinfo", vec![filter]).await?;
+
+    Ok(buffers
+        .as_array()
+        .map(|buffers| {
+            buffers
+                .iter()
+                .map(|buffer| {
+                    let field = |key: &str| {
+                        buffer.as_map().and_then(|entries| {
+                            entries
+                                .iter()
+                                .find(|(name, _)| name.as_str() == Some(key))
+                                .map(|(_, value)| value.clone())
+                        })
+                    };
+                    match field("name").and_then(|name| name.as_str().map(String::from)) {
+                        Some(name) if !name.is_empty() => name,
+                        _ => format!(
+                            "[No Name {}]",
+                            field("bufnr")
+                                .and_then(|number| number.as_u64())
+                                .unwrap_or(0)
+                        ),
+                    }
+                })
+                .collect()
+        })
+        .unwrap_or_default())
+}
+
+async fn open_files(
+    nvim: &Neovim<NeovimWriter>,
+    bridge: &Bridge,
+    files: Vec<String>,
+    in_tabs: bool,
+    wait_id: Option<u64>,
+) -> Result<(), Box<CallError>> {
+    let open_command = if in_tabs { "tab drop" } else { "drop" };
+    for file in files {
+        let escaped_file = nvim
+            .call_function("fnameescape", vec![Value::from(file.as_str())])
+            .await?;
+        let escaped_file = escaped_file.as_str().unwrap_or(&file);
+        nvim.command(&format!("{} {}", open_command, escaped_file))
+            .await?;
+
+        // The waiting client is told once the buffer goes away
+        if let Some(wait_id) = wait_id {
+            nvim.command(&format!(
+                "autocmd BufUnload <buffer> ++once call rpcnotify({}, 'neovide_remote_done', {})",
+                bridge.channel(),
+                wait_id
+            ))
+            .await?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,86 @@
+use std::time::{Duration, Instant};
+
+use log::trace;
+use parking_lot::Mutex;
+
+lazy_static! {
+    pub static ref CLOCK: Clock = Clock::new();
+}
+
+// Where the clock gets the current time from. The manual source only moves forward when
+// explicitly stepped which makes animations and blink timing reproducible frame by frame.
+#[derive(Debug, Clone, Copy)]
+pub enum TimeSource {
+    System,
+    Manual(Instant),
+}
+
+// The Clock is the single time source for anything animated or scheduled. Subsystems should ask
+// the clock for the current time and the frame delta instead of reading Instant::now directly.
+pub struct Clock {
+    source: Mutex<TimeSource>,
+    pending_step: Mutex<Duration>,
+}
+
+impl Clock {
+    pub fn new() -> Clock {
+        Clock {
+            source: Mutex::new(TimeSource::System),
+            pending_step: Mutex::new(Duration::from_secs(0)),
+        }
+    }
+
+    pub fn now(&self) -> Instant {
+        match *self.source.lock() {
+            TimeSource::System => Instant::now(),
+            TimeSource::Manual(instant) => instant,
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        match *self.source.lock() {
+            TimeSource::System => false,
+            TimeSource::Manual(_) => true,
+        }
+    }
+
+    // Freezes time at the current instant. Subsequent frames get a zero delta until the clock is
+    // either stepped or resumed.
+    pub fn pause(&self) {
+        let mut source = self.source.lock();
+        if let TimeSource::System = *source {
+            trace!("Clock paused");
+            *source = TimeSource::Manual(Instant::now());
+        }
+    }
+
+    pub fn resume(&self) {
+        trace!("Clock resumed");
+        *self.source.lock() = TimeSource::System;
+        *self.pending_step.lock() = Duration::from_secs(0);
+    }
+
+    // Moves a paused clock forward. The stepped duration is handed out as the delta of the next
+    // frame. Stepping a running clock does nothing.
+    pub fn step(&self, duration: Duration) {
+        let mut source = self.source.lock();
+        if let TimeSource::Manual(instant) = *source {
+            trace!("Clock stepped by {:?}", duration);
+            *source = TimeSource::Manual(instant + duration);
+            *self.pending_step.lock() += duration;
+        }
+    }
+
+    // Returns the time in seconds animations should advance by this frame.
+    pub fn frame_delta(&self, frame_length: Duration) -> f32 {
+        match *self.source.lock() {
+            TimeSource::System => frame_length.as_secs_f32(),
+            TimeSource::Manual(_) => {
+                let mut pending_step = self.pending_step.lock();
+                let delta = pending_step.as_secs_f32();
+                *pending_step = Duration::from_secs(0);
+                delta
+            }
+        }
+    }
+}
@@ -0,0 +1,244 @@
+use parking_lot::RwLock;
+
+use crate::remote;
+
+// The command line Neovide was started with. The binary hands it to run, and everything which
+// depends on it reads it from here rather than from the process, so that using the library never
+// looks at the arguments of whatever program embeds it.
+lazy_static! {
+    static ref ARGUMENTS: RwLock<Vec<String>> = RwLock::new(Vec::new());
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum OptionValue {
+    None,
+    Required,
+    // Only taken if the next argument isn't another option.
+    Optional,
+}
+
+// Neovide's own options. They are not passed on to nvim, and long ones may also be written as
+// --option=value. The remote flags are looked up in the remote module.
+const NEOVIDE_OPTIONS: &[(&str, OptionValue)] = &[
+    ("--log", OptionValue::None),
+    ("--geometry", OptionValue::Required),
+    ("--maximized", OptionValue::None),
+    ("--wsl", OptionValue::None),
+    ("--attach", OptionValue::Optional),
+];
+
+// The nvim options which take a value, so that the value isn't mistaken for a file to open.
+const NEOVIM_OPTIONS: &[(&str, OptionValue)] = &[
+    ("-c", OptionValue::Required),
+    ("--cmd", OptionValue::Required),
+    ("-i", OptionValue::Required),
+    ("-u", OptionValue::Required),
+    ("-s", OptionValue::Required),
+    ("-w", OptionValue::Required),
+    ("-W", OptionValue::Required),
+    ("-t", OptionValue::Required),
+    ("-T", OptionValue::Required),
+    ("--listen", OptionValue::Required),
+    ("--startuptime", OptionValue::Required),
+    ("-q", OptionValue::Optional),
+    ("-S", OptionValue::Optional),
+];
+
+// One option together with its value, or a single file argument, along with the arguments it
+// was made of.
+#[derive(Debug, PartialEq)]
+struct ArgumentGroup<'a> {
+    arguments: &'a [String],
+    option: Option<&'a str>,
+    value: Option<&'a str>,
+    neovide: bool,
+}
+
+fn neovide_option(name: &str) -> Option<OptionValue> {
+    if remote::is_remote_argument(name) {
+        return Some(OptionValue::None);
+    }
+
+    NEOVIDE_OPTIONS
+        .iter()
+        .find(|(option, _)| *option == name)
+        .map(|(_, value)| *value)
+}
+
+fn neovim_option(name: &str) -> OptionValue {
+    NEOVIM_OPTIONS
+        .iter()
+        .find(|(option, _)| *option == name)
+        .map_or(OptionValue::None, |(_, value)| *value)
+}
+
+// Groups the arguments after the program name. Everything starting with - or + is an option of
+// Neovide or nvim, and everything after -- is a file.
+fn group_arguments(arguments: &[String]) -> Vec<ArgumentGroup<'_>> {
+    let mut groups = Vec::new();
+    let mut only_files = false;
+    let mut index = 1;
+
+    while index < arguments.len() {
+        let argument = arguments[index].as_str();
+
+        if only_files || !(argument.starts_with('-') || argument.starts_with('+')) {
+            groups.push(ArgumentGroup {
+                arguments: &arguments[index..index + 1],
+                option: None,
+                value: None,
+                neovide: false,
+            });
+            index += 1;
+            continue;
+        }
+
+        only_files = argument == "--";
+
+        let (name, inline_value) = match argument.find('=') {
+            Some(position) if argument.starts_with("--") => {
+                (&argument[..position], Some(&argument[position + 1..]))
+            }
+            _ => (argument, None),
+        };
+
+        let (name, inline_value, neovide, value_kind) = match neovide_option(name) {
+            Some(value_kind) => (name, inline_value, true, value_kind),
+            None => (argument, None, false, neovim_option(argument)),
+        };
+
+        let next = arguments.get(index + 1).map(String::as_str);
+        let value = match (inline_value, value_kind, next) {
+            (Some(value), _, _) => Some(value),
+            (None, OptionValue::Required, Some(next)) => Some(next),
+            (None, OptionValue::Optional, Some(next)) if !next.starts_with('-') => Some(next),
+            _ => None,
+        };
+        let length = if value.is_some() && inline_value.is_none() {
+            2
+        } else {
+            1
+        };
+
+        groups.push(ArgumentGroup {
+            arguments: &arguments[index..index + length],
+            option: Some(name),
+            value,
+            neovide,
+        });
+        index += length;
+    }
+
+    groups
+}
+
+pub fn set_arguments(arguments: Vec<String>) {
+    *ARGUMENTS.write() = arguments;
+}
+
+pub fn arguments() -> Vec<String> {
+    ARGUMENTS.read().clone()
+}
+
+pub fn has_argument(argument: &str) -> bool {
+    ARGUMENTS.read().iter().any(|arg| arg == argument)
+}
+
+pub fn log_to_file() -> bool {
+    has_argument("--log")
+}
+
+// The value of one of Neovide's options. None if the option wasn't passed, Some(None) if it was
+// passed without a value.
+pub fn option_value(name: &str) -> Option<Option<String>> {
+    let arguments = ARGUMENTS.read();
+    let group = group_arguments(&arguments)
+        .into_iter()
+        .find(|group| group.neovide && group.option == Some(name))?;
+    Some(group.value.map(String::from))
+}
+
+// The files to open, without any option values.
+pub fn file_arguments() -> Vec<String> {
+    let arguments = ARGUMENTS.read();
+    group_arguments(&arguments)
+        .into_iter()
+        .filter(|group| group.option.is_none())
+        .flat_map(|group| group.arguments.iter().cloned())
+        .collect()
+}
+
+// Everything Neovide doesn't handle itself is passed on to nvim. The first argument is still the
+// program name.
+pub fn neovim_arguments() -> Vec<String> {
+    let arguments = ARGUMENTS.read();
+    arguments
+        .iter()
+        .take(1)
+        .chain(
+            group_arguments(&arguments)
+                .into_iter()
+                .filter(|group| !group.neovide)
+                .flat_map(|group| group.arguments.iter()),
+        )
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn arguments(arguments: &[&str]) -> Vec<String> {
+        arguments
+            .iter()
+            .map(|argument| argument.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn option_values_are_not_files() {
+        let arguments = arguments(&[
+            "neovide",
+            "--geometry",
+            "80x24",
+            "-u",
+            "init.vim",
+            "--remote",
+            "file.txt",
+        ]);
+        let files: Vec<_> = group_arguments(&arguments)
+            .into_iter()
+            .filter(|group| group.option.is_none())
+            .map(|group| group.arguments)
+            .collect();
+        assert_eq!(files, vec![&arguments[6..7]]);
+    }
+
+    #[test]
+    fn neovide_options_take_inline_and_optional_values() {
+        let arguments = arguments(&["neovide", "--geometry=80x24", "--attach", "-c", "echo"]);
+        let groups = group_arguments(&arguments);
+
+        assert_eq!(groups[0].option, Some("--geometry"));
+        assert_eq!(groups[0].value, Some("80x24"));
+        assert!(groups[0].neovide);
+        assert_eq!(groups[1].option, Some("--attach"));
+        assert_eq!(groups[1].value, None);
+        assert!(groups[1].neovide);
+        assert_eq!(groups[2].arguments, &arguments[3..5]);
+        assert!(!groups[2].neovide);
+    }
+
+    #[test]
+    fn arguments_after_double_dash_are_files() {
+        let arguments = arguments(&["neovide", "+10", "--", "-file", "--log"]);
+        let groups = group_arguments(&arguments);
+
+        assert_eq!(groups[0].option, Some("+10"));
+        assert_eq!(groups[1].option, Some("--"));
+        assert_eq!(groups[2].option, None);
+        assert_eq!(groups[3].option, None);
+        assert!(groups.iter().all(|group| !group.neovide));
+    }
+}
@@ -0,0 +1,168 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::Write as _;
+use std::fs;
+use std::panic::{self, PanicInfo};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use backtrace::Backtrace;
+use parking_lot::Mutex;
+use rmpv::Value;
+use skulpin::sdl2::messagebox::{show_simple_message_box, MessageBoxFlag};
+
+use crate::bridge::UiCommand;
+use crate::paths::{data_directory, private_directory};
+use crate::settings::SETTINGS;
+
+// Number of redraw events and ui commands kept around for the crash report.
+const HISTORY_LENGTH: usize = 100;
+
+lazy_static! {
+    pub static ref CRASH_REPORTER: CrashReporter = CrashReporter::new();
+}
+
+// Keeps a short history of what Neovide was doing so that a panic can be written out with enough
+// context to reproduce it. Redraw events are shared with the redraw handler rather than copied, so
+// keeping their payloads around costs little more than the event history itself.
+pub struct CrashReporter {
+    redraw_events: Mutex<VecDeque<(String, Arc<Value>)>>,
+    ui_commands: Mutex<VecDeque<UiCommand>>,
+    gui_state: Mutex<BTreeMap<&'static str, String>>,
+}
+
+fn push_bounded<T>(history: &mut VecDeque<T>, item: T) {
+    if history.len() == HISTORY_LENGTH {
+        history.pop_front();
+    }
+    history.push_back(item);
+}
+
+impl CrashReporter {
+    pub fn new() -> CrashReporter {
+        CrashReporter {
+            redraw_events: Mutex::new(VecDeque::with_capacity(HISTORY_LENGTH)),
+            ui_commands: Mutex::new(VecDeque::with_capacity(HISTORY_LENGTH)),
+            gui_state: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn record_redraw_event(&self, event_name: &str, event: Arc<Value>) {
+        push_bounded(
+            &mut self.redraw_events.lock(),
+            (event_name.to_string(), event),
+        );
+    }
+
+    pub fn record_ui_command(&self, command: &UiCommand) {
+        push_bounded(&mut self.ui_commands.lock(), command.clone());
+    }
+
+    // Stores a piece of font or window state to be included in the report.
+    pub fn set_gui_state(&self, key: &'static str, value: String) {
+        self.gui_state.lock().insert(key, value);
+    }
+
+    // The panic may have happened while one of the locks was held, so everything here only tries
+    // to lock and skips the section otherwise.
+    fn build_report(&self, info: &PanicInfo) -> String {
+        let mut report = String::new();
+
+        writeln!(report, "Neovide {} crash report", env!("CARGO_PKG_VERSION")).ok();
+        writeln!(
+            report,
+            "Thread: {}",
+            std::thread::current().name().unwrap_or("<unnamed>")
+        )
+        .ok();
+        writeln!(report, "Panic: {}", info).ok();
+        writeln!(report, "\nBacktrace:\n{:?}", Backtrace::new()).ok();
+
+        writeln!(report, "\nGui state:").ok();
+        match self.gui_state.try_lock() {
+            Some(gui_state) => {
+                for (key, value) in gui_state.iter() {
+                    writeln!(report, "  {}: {}", key, value).ok();
+                }
+            }
+            None => {
+                writeln!(report, "  <unavailable>").ok();
+            }
+        }
+
+        writeln!(report, "\nSettings:").ok();
+        match SETTINGS.try_read_values() {
+            Some(values) => {
+                for (name, value) in values {
+                    writeln!(report, "  neovide_{} = {}", name, value).ok();
+                }
+            }
+            None => {
+                writeln!(report, "  <unavailable>").ok();
+            }
+        }
+
+        writeln!(report, "\nRecent ui commands (oldest first):").ok();
+        match self.ui_commands.try_lock() {
+            Some(ui_commands) => {
+                for command in ui_commands.iter() {
+                    writeln!(report, "  {:?}", command).ok();
+                }
+            }
+            None => {
+                writeln!(report, "  <unavailable>").ok();
+            }
+        }
+
+        writeln!(report, "\nRecent redraw events (oldest first):").ok();
+        match self.redraw_events.try_lock() {
+            Some(redraw_events) => {
+                for (event_name, event) in redraw_events.iter() {
+                    writeln!(report, "  {} {}", event_name, event).ok();
+                }
+            }
+            None => {
+                writeln!(report, "  <unavailable>").ok();
+            }
+        }
+
+        report
+    }
+
+    // Reports go to a directory only the user can read, since they contain recent commands and
+    // settings.
+    fn write_report(&self, info: &PanicInfo) -> Option<PathBuf> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+        let directory = private_directory(&data_directory()?.join("crash_reports")).ok()?;
+        let path = directory.join(format!("crash_{}_{}.txt", timestamp, std::process::id()));
+
+        fs::write(&path, self.build_report(info)).ok().map(|_| path)
+    }
+}
+
+// Chains onto the default panic hook. After the usual message is printed, a crash report is
+// written to the data directory and its location is shown to the user.
+pub fn install_panic_hook() {
+    let default_hook = panic::take_hook();
+
+    panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+
+        let message = match CRASH_REPORTER.write_report(info) {
+            Some(path) => format!(
+                "Neovide crashed. A crash report was written to {}",
+                path.display()
+            ),
+            None => String::from("Neovide crashed and the crash report could not be written."),
+        };
+        eprintln!("{}", message);
+
+        // Message boxes are only safe to show from the thread which owns the window.
+        if std::thread::current().name() == Some("main") {
+            show_simple_message_box(MessageBoxFlag::ERROR, "Neovide", &message, None).ok();
+        }
+    }));
+}
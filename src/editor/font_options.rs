@@ -0,0 +1,214 @@
+use std::fmt;
+
+use log::warn;
+
+// A parsed 'guifont' value. Fonts are separated by commas and tried in order, options follow the
+// font names after colons:
+//
+//   h<size>     font size in points
+//   b, i        bold and italic
+//   w<width>    cell width in points instead of the width of the font's glyphs
+//   W<weight>   weight as a number from 100 to 900 or as a name such as Light or Medium
+//
+// Weight names can also be given as options of their own, as in "Iosevka:Light:h12". Underscores
+// in font names stand for spaces.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FontOptions {
+    pub font_list: Vec<String>,
+    pub size: Option<f32>,
+    pub bold: bool,
+    pub italic: bool,
+    pub width: Option<f32>,
+    pub weight: Option<f32>,
+}
+
+const WEIGHT_NAMES: &[(&str, f32)] = &[
+    ("thin", 100.0),
+    ("extralight", 200.0),
+    ("light", 300.0),
+    ("normal", 400.0),
+    ("regular", 400.0),
+    ("medium", 500.0),
+    ("semibold", 600.0),
+    ("bold", 700.0),
+    ("extrabold", 800.0),
+    ("black", 900.0),
+];
+
+fn parse_weight(weight: &str) -> Option<f32> {
+    if let Ok(weight) = weight.parse::<f32>() {
+        return Some(weight.max(1.0).min(1000.0));
+    }
+
+    let weight = weight.to_lowercase();
+    WEIGHT_NAMES
+        .iter()
+        .find(|(name, _)| *name == weight)
+        .map(|(_, value)| *value)
+}
+
+impl FontOptions {
+    pub fn parse(guifont: &str) -> FontOptions {
+        let mut options = FontOptions::default();
+
+        for entry in guifont.split(',') {
+            let mut parts = entry.split(':');
+            let font_name = parts.next().unwrap_or_default().trim().replace('_', " ");
+            if !font_name.is_empty() {
+                options.font_list.push(font_name);
+            }
+
+            for part in parts.filter(|part| !part.is_empty()) {
+                options.parse_option(part);
+            }
+        }
+
+        options
+    }
+
+    // Like parse, but a guifont without font names such as ":h16", or an empty one, keeps the fonts
+    // currently in use and only changes the options.
+    pub fn parse_with_current(guifont: &str, current: Option<&FontOptions>) -> FontOptions {
+        let mut options = FontOptions::parse(guifont);
+        if options.font_list.is_empty() {
+            if let Some(current) = current {
+                options.font_list = current.font_list.clone();
+            }
+        }
+        options
+    }
+
+    fn parse_option(&mut self, option: &str) {
+        let mut characters = option.chars();
+        let kind = characters.next();
+        let value = characters.as_str();
+        match kind {
+            Some('h') if !value.is_empty() => self.size = value.parse::<f32>().ok(),
+            Some('w') if !value.is_empty() => self.width = value.parse::<f32>().ok(),
+            Some('W') if !value.is_empty() => self.weight = parse_weight(value),
+            Some('b') if value.is_empty() => self.bold = true,
+            Some('i') if value.is_empty() => self.italic = true,
+            // Underline, strikeout, charset and quality only mean something to Vim on Windows
+            Some('u') | Some('s') if value.is_empty() => {}
+            Some('c') | Some('q') => {}
+            _ => match parse_weight(option) {
+                Some(weight) => self.weight = Some(weight),
+                None => warn!("Ignoring unknown guifont option {}", option),
+            },
+        }
+    }
+
+    pub fn primary_font(&self) -> Option<&str> {
+        self.font_list.first().map(String::as_str)
+    }
+}
+
+// Writes the options back in guifont syntax. Also used to key caches by font.
+impl fmt::Display for FontOptions {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "{}", self.font_list.join(","))?;
+        if let Some(size) = self.size {
+            write!(formatter, ":h{}", size)?;
+        }
+        if let Some(width) = self.width {
+            write!(formatter, ":w{}", width)?;
+        }
+        if let Some(weight) = self.weight {
+            write!(formatter, ":W{}", weight)?;
+        }
+        if self.bold {
+            write!(formatter, ":b")?;
+        }
+        if self.italic {
+            write!(formatter, ":i")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_reads_fallback_list() {
+        let options = FontOptions::parse("Iosevka, Fira Code,Noto Color Emoji:h12");
+        assert_eq!(
+            options.font_list,
+            vec!["Iosevka", "Fira Code", "Noto Color Emoji"]
+        );
+        assert_eq!(options.primary_font(), Some("Iosevka"));
+        assert_eq!(options.size, Some(12.0));
+    }
+
+    #[test]
+    fn parse_replaces_underscores() {
+        let options = FontOptions::parse("Fira_Code,Source_Code_Pro");
+        assert_eq!(options.font_list, vec!["Fira Code", "Source Code Pro"]);
+    }
+
+    #[test]
+    fn parse_reads_size_width_and_weight() {
+        let options = FontOptions::parse("Iosevka:h13.5:w8:W300:b:i");
+        assert_eq!(options.size, Some(13.5));
+        assert_eq!(options.width, Some(8.0));
+        assert_eq!(options.weight, Some(300.0));
+        assert!(options.bold);
+        assert!(options.italic);
+    }
+
+    #[test]
+    fn parse_clamps_numeric_weights() {
+        assert_eq!(FontOptions::parse("Iosevka:W0").weight, Some(1.0));
+        assert_eq!(FontOptions::parse("Iosevka:W5000").weight, Some(1000.0));
+    }
+
+    #[test]
+    fn parse_reads_named_weights() {
+        assert_eq!(FontOptions::parse("Iosevka:WMedium").weight, Some(500.0));
+        assert_eq!(FontOptions::parse("Iosevka:Light:h12").weight, Some(300.0));
+        assert_eq!(FontOptions::parse("Iosevka:SemiBold").weight, Some(600.0));
+        assert_eq!(FontOptions::parse("Iosevka:WHeavyish").weight, None);
+    }
+
+    #[test]
+    fn parse_ignores_unknown_options() {
+        let options = FontOptions::parse("Iosevka:x:u:s:cANSI:qDRAFT:hbig:h14");
+        assert_eq!(options.font_list, vec!["Iosevka"]);
+        assert_eq!(options.size, Some(14.0));
+        assert_eq!(options.weight, None);
+        assert!(!options.bold);
+        assert!(!options.italic);
+    }
+
+    #[test]
+    fn parse_accepts_empty_guifont() {
+        assert_eq!(FontOptions::parse(""), FontOptions::default());
+        assert_eq!(FontOptions::parse(":h16").font_list, Vec::<String>::new());
+    }
+
+    #[test]
+    fn size_only_guifont_keeps_current_fonts() {
+        let current = FontOptions::parse("Iosevka,Fira_Code:h12:b");
+        let options = FontOptions::parse_with_current(":h16", Some(&current));
+        assert_eq!(options.font_list, vec!["Iosevka", "Fira Code"]);
+        assert_eq!(options.size, Some(16.0));
+        assert!(!options.bold);
+
+        let options = FontOptions::parse_with_current("", Some(&current));
+        assert_eq!(options.font_list, current.font_list);
+        assert_eq!(options.size, None);
+
+        let options = FontOptions::parse_with_current("Hack:h10", Some(&current));
+        assert_eq!(options.font_list, vec!["Hack"]);
+
+        let options = FontOptions::parse_with_current(":h16", None);
+        assert!(options.font_list.is_empty());
+    }
+
+    #[test]
+    fn display_writes_guifont_syntax() {
+        let options = FontOptions::parse("Fira_Code,Iosevka:h12:w7:W600:b:i");
+        assert_eq!(options.to_string(), "Fira Code,Iosevka:h12:w7:W600:b:i");
+    }
+}
@@ -0,0 +1,31 @@
+use std::sync::Arc;
+
+use image::RgbaImage;
+
+// An image placed over the grid through the image api. Positions and sizes are in cells so the
+// image follows font and window size changes.
+#[derive(new, Debug, Clone)]
+pub struct InlineImage {
+    pub grid: u64,
+    pub row: i64,
+    pub column: u64,
+    pub width: u64,
+    pub height: u64,
+    pub pixels: Arc<RgbaImage>,
+}
+
+impl InlineImage {
+    // Images overlapping a scrolled region move with the text, even when only partially visible
+    // so that scrolling back brings them back.
+    pub fn scroll(&mut self, grid: u64, top: u64, bottom: u64, left: u64, right: u64, rows: i64) {
+        let inside_region = self.grid == grid
+            && self.row + self.height as i64 > top as i64
+            && self.row < bottom as i64
+            && self.column >= left
+            && self.column < right;
+
+        if inside_region {
+            self.row -= rows;
+        }
+    }
+}
@@ -1,56 +1,61 @@
 mod cursor;
+mod font_options;
 mod grid;
+mod inline_image;
 mod style;
 
 use std::collections::HashMap;
 use std::sync::Arc;
 
 use log::trace;
-use parking_lot::Mutex;
 use skulpin::skia_safe::colors;
 use unicode_segmentation::UnicodeSegmentation;
 
 use crate::bridge::{EditorMode, GridLineCell, GuiOption, RedrawEvent};
-use crate::redraw_scheduler::REDRAW_SCHEDULER;
-use crate::window::window_geometry_or_default;
+use crate::redraw_scheduler::RedrawScheduler;
 pub use cursor::{Cursor, CursorMode, CursorShape};
+pub use font_options::FontOptions;
 pub use grid::CharacterGrid;
+pub use inline_image::InlineImage;
 pub use style::{Colors, Style};
 
-lazy_static! {
-    pub static ref EDITOR: Arc<Mutex<Editor>> = Arc::new(Mutex::new(Editor::new()));
-}
-
 #[derive(new, Debug, Clone)]
 pub struct DrawCommand {
     pub text: String,
     pub cell_width: u64,
     pub grid_position: (u64, u64),
     pub style: Option<Arc<Style>>,
+    // Set for single graphemes spanning two cells, which are drawn with the guifontwide font.
+    #[new(default)]
+    pub double_width: bool,
 }
 
 pub struct Editor {
     pub grid: CharacterGrid,
     pub title: String,
     pub mouse_enabled: bool,
-    pub font_name: Option<String>,
-    pub font_size: Option<f32>,
+    pub font_options: Option<FontOptions>,
+    pub font_wide_options: Option<FontOptions>,
     pub cursor: Cursor,
     pub default_style: Arc<Style>,
     pub defined_styles: HashMap<u64, Arc<Style>>,
     pub previous_style: Option<Arc<Style>>,
     pub mode_list: Vec<CursorMode>,
     pub current_mode: EditorMode,
+    pub images: HashMap<u64, InlineImage>,
+    redraw_scheduler: Arc<RedrawScheduler>,
 }
 
 impl Editor {
-    pub fn new() -> Editor {
+    // Each window has its own editor. Flushes queue a frame on that window's scheduler. The grid
+    // starts out with the given size until nvim resizes it.
+    pub fn new(redraw_scheduler: Arc<RedrawScheduler>, dimensions: (u64, u64)) -> Editor {
         let mut editor = Editor {
-            grid: CharacterGrid::new(window_geometry_or_default()),
+            grid: CharacterGrid::new(dimensions),
             title: "Neovide".to_string(),
             mouse_enabled: true,
-            font_name: None,
-            font_size: None,
+            font_options: None,
+            font_wide_options: None,
             cursor: Cursor::new(),
             default_style: Arc::new(Style::new(Colors::new(
                 Some(colors::WHITE),
@@ -61,6 +66,8 @@ impl Editor {
             previous_style: None,
             mode_list: Vec::new(),
             current_mode: EditorMode::Unknown(String::from("")),
+            images: HashMap::new(),
+            redraw_scheduler,
         };
 
         editor.grid.clear();
@@ -94,7 +101,7 @@ impl Editor {
             }
             RedrawEvent::Flush => {
                 trace!("Image flushed");
-                REDRAW_SCHEDULER.queue_next_frame();
+                self.redraw_scheduler.queue_next_frame();
             }
             RedrawEvent::Resize { width, height, .. } => self.grid.resize(width, height),
             RedrawEvent::DefaultColorsSet { colors } => {
@@ -112,14 +119,19 @@ impl Editor {
             RedrawEvent::Clear { .. } => self.grid.clear(),
             RedrawEvent::CursorGoto { row, column, .. } => self.cursor.position = (row, column),
             RedrawEvent::Scroll {
+                grid,
                 top,
                 bottom,
                 left,
                 right,
                 rows,
                 columns,
-                ..
-            } => self.scroll_region(top, bottom, left, right, rows, columns),
+            } => {
+                self.scroll_region(top, bottom, left, right, rows, columns);
+                for image in self.images.values_mut() {
+                    image.scroll(grid, top, bottom, left, right, rows);
+                }
+            }
             _ => {}
         };
     }
@@ -167,6 +179,13 @@ impl Editor {
             }
 
             for (col_index, cell) in row.iter().enumerate() {
+                // Double width graphemes are followed by an empty cell and get a command of
+                // their own, which the empty cell then completes.
+                let double_width = match row.get(col_index + 1) {
+                    Some(Some((next_character, _))) => next_character.is_empty(),
+                    _ => false,
+                };
+
                 if let Some((character, style)) = cell {
                     if character.is_empty() {
                         add_character(
@@ -179,7 +198,7 @@ impl Editor {
                         add_command(&mut draw_commands, command);
                         command = None;
                     } else {
-                        if !command_matches(&command, &style) {
+                        if double_width || !command_matches(&command, &style) {
                             add_command(&mut draw_commands, command);
                             command = None;
                         }
@@ -190,6 +209,9 @@ impl Editor {
                             col_index as u64,
                             style.clone(),
                         );
+                        if let Some(command) = command.as_mut() {
+                            command.double_width = double_width;
+                        }
                     }
                 } else {
                     if !command_matches(&command, &None) {
@@ -307,14 +329,17 @@ impl Editor {
     fn set_option(&mut self, gui_option: GuiOption) {
         trace!("Option set {:?}", &gui_option);
         match gui_option {
-            GuiOption::GuiFont(font_description) => {
-                let parts: Vec<&str> = font_description.split(':').collect();
-                self.font_name = Some(parts[0].to_string());
-                for part in parts.iter().skip(1) {
-                    if part.starts_with('h') && part.len() > 1 {
-                        self.font_size = part[1..].parse::<f32>().ok();
-                    }
-                }
+            GuiOption::GuiFont(guifont) => {
+                self.font_options = Some(FontOptions::parse_with_current(
+                    &guifont,
+                    self.font_options.as_ref(),
+                ));
+            }
+            // The cell size doesn't depend on the wide font, so nothing else would cause the
+            // double width characters already on screen to be drawn again.
+            GuiOption::GuiFontWide(guifontwide) => {
+                self.font_wide_options = Some(FontOptions::parse(&guifontwide));
+                self.grid.set_dirty_all(true);
             }
             _ => {}
         }
@@ -1,4 +1,38 @@
+use std::fmt::Display;
+use std::time::{Duration, Instant};
+
 use log::error;
+use parking_lot::Mutex;
+
+use crate::clock::CLOCK;
+use crate::redraw_scheduler::RedrawScheduler;
+
+// How long the error indicator stays on screen after the most recent recoverable error.
+const ERROR_INDICATOR_DURATION: Duration = Duration::from_secs(5);
+
+lazy_static! {
+    static ref LAST_RECOVERED_ERROR: Mutex<Option<Instant>> = Mutex::new(None);
+}
+
+// Logs an error which Neovide can continue running after, and flags it so the renderer can show
+// the error indicator.
+pub fn report_recoverable_error<E: Display>(explanation: &str, error: E) {
+    error!("{}: {}", explanation, error);
+
+    let now = CLOCK.now();
+    *LAST_RECOVERED_ERROR.lock() = Some(now);
+    RedrawScheduler::for_each(|redraw_scheduler| {
+        redraw_scheduler.queue_next_frame();
+        redraw_scheduler.schedule(now + ERROR_INDICATOR_DURATION);
+    });
+}
+
+pub fn error_indicator_visible() -> bool {
+    LAST_RECOVERED_ERROR
+        .lock()
+        .map(|reported| CLOCK.now() < reported + ERROR_INDICATOR_DURATION)
+        .unwrap_or(false)
+}
 
 fn show_error(explanation: &str) -> ! {
     error!("{}", explanation);
@@ -0,0 +1,273 @@
+use std::cell::RefCell;
+use std::fs::{self, File};
+use std::io::{BufReader, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use image::{Rgba, RgbaImage};
+use log::{error, info};
+use parking_lot::Mutex;
+use rmpv::{decode::read_value, Value};
+use skulpin::skia_safe::{EncodedImageFormat, Surface};
+
+use crate::bridge::parse_redraw_event;
+use crate::clock::CLOCK;
+use crate::editor::{Editor, FontOptions};
+use crate::redraw_scheduler::RedrawScheduler;
+use crate::renderer::{FontCaches, Renderer};
+use crate::INITIAL_DIMENSIONS;
+
+// The golden harness replays recorded redraw events through the Editor, renders the result
+// offscreen and compares it against a reference png. Each case is a directory containing an
+// events.mpack file with a sequence of msgpack encoded redraw notification arguments, exactly
+// as nvim sends them.
+const EVENTS_FILE: &str = "events.mpack";
+const REFERENCE_FILE: &str = "reference.png";
+const ACTUAL_FILE: &str = "actual.png";
+const DIFF_FILE: &str = "diff.png";
+
+const GOLDEN_FONT_NAME: &str = "Golden Mono";
+const GOLDEN_FONT_SIZE: f32 = 14.0;
+const GOLDEN_FONT_DATA: &[u8] = include_bytes!("../tests/golden/fonts/DejaVuSansMono.ttf");
+
+// Maximum difference allowed in a single color channel before a pixel counts as changed, and the
+// fraction of changed pixels tolerated before a case fails. Both absorb antialiasing noise
+// between platforms.
+const CHANNEL_TOLERANCE: u8 = 24;
+const PIXEL_TOLERANCE: f32 = 0.002;
+
+fn read_events(path: &Path) -> Result<Vec<Value>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut notifications = Vec::new();
+
+    loop {
+        match read_value(&mut reader) {
+            Ok(value) => notifications.push(value),
+            Err(rmpv::decode::Error::InvalidMarkerRead(error))
+                if error.kind() == ErrorKind::UnexpectedEof =>
+            {
+                break
+            }
+            Err(error) => return Err(anyhow!("Could not decode {:?}: {}", path, error)),
+        }
+    }
+
+    Ok(notifications)
+}
+
+fn replay_events(
+    notifications: Vec<Value>,
+    editor: &Mutex<Editor>,
+    redraw_scheduler: &Arc<RedrawScheduler>,
+) -> Result<()> {
+    let mut editor = editor.lock();
+    *editor = Editor::new(redraw_scheduler.clone(), INITIAL_DIMENSIONS);
+
+    for notification in notifications {
+        let batches = match notification {
+            Value::Array(batches) => batches,
+            other => return Err(anyhow!("Expected redraw arguments, but received {}", other)),
+        };
+
+        for batch in batches {
+            for event in parse_redraw_event(batch)? {
+                editor.handle_redraw_event(event);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn render_case(renderer: &mut Renderer, editor: &Mutex<Editor>) -> Result<RgbaImage> {
+    let (grid_width, grid_height) = {
+        let editor = editor.lock();
+        (editor.grid.width, editor.grid.height)
+    };
+    let width = (grid_width as f32 * renderer.font_width).ceil() as i32;
+    let height = (grid_height as f32 * renderer.font_height).ceil() as i32;
+
+    let mut surface = Surface::new_raster_n32_premul((width, height))
+        .ok_or_else(|| anyhow!("Could not create a {}x{} surface", width, height))?;
+
+    // Let every animation settle so that only the final frame is compared
+    CLOCK.step(Duration::from_secs(1));
+    let dt = CLOCK.frame_delta(Duration::from_secs(0));
+    renderer.draw_to_canvas(surface.canvas(), 1.0, (width as f32, height as f32), dt);
+
+    let data = surface
+        .image_snapshot()
+        .encode_to_data(EncodedImageFormat::PNG)
+        .ok_or_else(|| anyhow!("Could not encode the rendered frame"))?;
+
+    Ok(image::load_from_memory(data.as_bytes())?.to_rgba())
+}
+
+fn pixel_at(image: &RgbaImage, x: u32, y: u32) -> Option<&Rgba<u8>> {
+    if x < image.width() && y < image.height() {
+        Some(image.get_pixel(x, y))
+    } else {
+        None
+    }
+}
+
+// Returns the number of changed pixels and an image marking each of them in red on top of a faded
+// copy of the reference.
+fn diff_images(reference: &RgbaImage, actual: &RgbaImage) -> (u32, RgbaImage) {
+    let width = reference.width().max(actual.width());
+    let height = reference.height().max(actual.height());
+    let mut diff = RgbaImage::new(width, height);
+    let mut changed_pixels = 0;
+
+    for y in 0..height {
+        for x in 0..width {
+            let expected = pixel_at(reference, x, y);
+            let received = pixel_at(actual, x, y);
+
+            let changed = match (expected, received) {
+                (Some(expected), Some(received)) => expected
+                    .0
+                    .iter()
+                    .zip(received.0.iter())
+                    .any(|(a, b)| (*a as i16 - *b as i16).abs() as u8 > CHANNEL_TOLERANCE),
+                _ => true,
+            };
+
+            let pixel = if changed {
+                changed_pixels += 1;
+                Rgba([255, 0, 0, 255])
+            } else {
+                let Rgba([r, g, b, _]) = *expected.unwrap();
+                Rgba([r / 4, g / 4, b / 4, 255])
+            };
+
+            diff.put_pixel(x, y, pixel);
+        }
+    }
+
+    (changed_pixels, diff)
+}
+
+// Everything a case is rendered with. The editor is reset before every case.
+struct GoldenRenderer {
+    editor: Arc<Mutex<Editor>>,
+    redraw_scheduler: Arc<RedrawScheduler>,
+    renderer: Renderer,
+}
+
+impl GoldenRenderer {
+    fn new() -> GoldenRenderer {
+        let redraw_scheduler = RedrawScheduler::new();
+        let editor = Arc::new(Mutex::new(Editor::new(
+            redraw_scheduler.clone(),
+            INITIAL_DIMENSIONS,
+        )));
+        let mut renderer = Renderer::new(
+            editor.clone(),
+            redraw_scheduler.clone(),
+            Rc::new(RefCell::new(FontCaches::new())),
+        );
+        renderer.register_font_data(GOLDEN_FONT_NAME, GOLDEN_FONT_DATA.to_vec());
+        renderer.set_font(&FontOptions {
+            font_list: vec![String::from(GOLDEN_FONT_NAME)],
+            size: Some(GOLDEN_FONT_SIZE),
+            ..FontOptions::default()
+        });
+
+        GoldenRenderer {
+            editor,
+            redraw_scheduler,
+            renderer,
+        }
+    }
+}
+
+fn run_case(case_directory: &Path, golden: &mut GoldenRenderer, bless: bool) -> Result<bool> {
+    replay_events(
+        read_events(&case_directory.join(EVENTS_FILE))?,
+        &golden.editor,
+        &golden.redraw_scheduler,
+    )?;
+    let actual = render_case(&mut golden.renderer, &golden.editor)?;
+
+    let reference_path = case_directory.join(REFERENCE_FILE);
+    if bless {
+        actual.save(&reference_path)?;
+        info!("Wrote reference image {:?}", reference_path);
+        return Ok(true);
+    }
+
+    // A missing reference fails the case, so that a case can't pass without ever being compared
+    if !reference_path.exists() {
+        actual.save(case_directory.join(ACTUAL_FILE))?;
+        error!(
+            "{:?} has no reference image. Check {} and bless it",
+            case_directory, ACTUAL_FILE
+        );
+        return Ok(false);
+    }
+
+    let reference = image::open(&reference_path)?.to_rgba();
+    let (changed_pixels, diff) = diff_images(&reference, &actual);
+    let total_pixels = diff.width() * diff.height();
+
+    if changed_pixels as f32 > total_pixels as f32 * PIXEL_TOLERANCE {
+        actual.save(case_directory.join(ACTUAL_FILE))?;
+        diff.save(case_directory.join(DIFF_FILE))?;
+        error!(
+            "{:?}: {} of {} pixels differ from the reference",
+            case_directory, changed_pixels, total_pixels
+        );
+        Ok(false)
+    } else {
+        fs::remove_file(case_directory.join(ACTUAL_FILE)).ok();
+        fs::remove_file(case_directory.join(DIFF_FILE)).ok();
+        Ok(true)
+    }
+}
+
+// Runs every case below the given directory. Returns true if all of them matched their reference
+// images. Blessing rewrites every reference instead of comparing against it.
+pub fn run(cases_directory: &Path, bless: bool) -> bool {
+    CLOCK.pause();
+
+    let mut golden = GoldenRenderer::new();
+
+    let mut case_directories = match fs::read_dir(cases_directory) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.join(EVENTS_FILE).exists())
+            .collect::<Vec<PathBuf>>(),
+        Err(error) => {
+            eprintln!(
+                "Could not read golden cases from {:?}: {}",
+                cases_directory, error
+            );
+            return false;
+        }
+    };
+    case_directories.sort();
+
+    let mut passed = true;
+    for case_directory in case_directories {
+        let result = run_case(&case_directory, &mut golden, bless)
+            .with_context(|| format!("Golden case {:?} failed to run", case_directory));
+
+        match result {
+            Ok(true) => println!("ok     {}", case_directory.display()),
+            Ok(false) => {
+                println!("FAILED {}", case_directory.display());
+                passed = false;
+            }
+            Err(error) => {
+                println!("ERROR  {}: {:#}", case_directory.display(), error);
+                passed = false;
+            }
+        }
+    }
+
+    passed
+}
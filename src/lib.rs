@@ -0,0 +1,91 @@
+// Neovide's ui model and renderer. The binary in main.rs is one consumer, but the redraw event
+// parser, the editor state and the renderer can be used on their own to replay or draw nvim's ui
+// from other programs.
+//
+// A minimal embedding parses redraw notifications with bridge::events::parse_redraw_event, feeds
+// the events to an editor::Editor and draws it with renderer::Renderer::draw_to_canvas onto any
+// Skia canvas. initialize_settings has to be called once before drawing.
+
+#[macro_use]
+pub mod settings;
+
+pub mod bridge;
+pub mod clock;
+pub mod editor;
+pub mod redraw_scheduler;
+pub mod renderer;
+
+mod command_line;
+mod crash_report;
+mod error_handling;
+mod paths;
+mod remote;
+mod window;
+mod window_state;
+
+#[cfg(feature = "golden-tests")]
+#[doc(hidden)]
+pub mod golden;
+
+#[macro_use]
+extern crate derive_new;
+#[macro_use]
+extern crate rust_embed;
+#[macro_use]
+extern crate lazy_static;
+
+use std::process;
+
+use flexi_logger::{Cleanup, Criterion, Duplicate, Logger, Naming};
+
+pub const INITIAL_DIMENSIONS: (u64, u64) = (100, 50);
+
+// Registers the defaults of every setting the editor and renderer read.
+pub fn initialize_settings() {
+    window::initialize_settings();
+    redraw_scheduler::initialize_settings();
+    renderer::initialize_settings();
+    renderer::cursor_renderer::initialize_settings();
+    bridge::layouts::initialize_settings();
+}
+
+fn start_logger() {
+    if command_line::log_to_file() {
+        Logger::with_env_or_str("neovide")
+            .duplicate_to_stderr(Duplicate::Error)
+            .log_to_file()
+            .rotate(
+                Criterion::Size(10_000_000),
+                Naming::Timestamps,
+                Cleanup::KeepLogFiles(1),
+            )
+            .start()
+            .expect("Could not start logger");
+    } else {
+        Logger::with_env_or_str("neovide = error")
+            .start()
+            .expect("Could not start logger");
+    }
+}
+
+// Runs the desktop application with the given command line, the first argument being the program
+// name. This is what the binary calls. It takes over the process, installing a panic hook and a
+// logger, and exits once the last window closes.
+pub fn run(arguments: Vec<String>) {
+    command_line::set_arguments(arguments);
+    crash_report::install_panic_hook();
+    start_logger();
+
+    if remote::forward_to_running_instance() {
+        process::exit(0);
+    }
+
+    if let Err(err) = window::window_geometry() {
+        eprintln!("{}", err);
+        process::exit(1);
+    };
+    initialize_settings();
+
+    remote::start_listening();
+    window::ui_loop();
+}
@@ -0,0 +1,79 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[cfg(windows)]
+fn data_home() -> Option<PathBuf> {
+    std::env::var_os("APPDATA").map(PathBuf::from)
+}
+
+#[cfg(target_os = "macos")]
+fn data_home() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+fn data_home() -> Option<PathBuf> {
+    std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+}
+
+// Neovide's own directory in the user's data directory. Window state and crash reports go here.
+pub fn data_directory() -> Option<PathBuf> {
+    data_home().map(|data_home| data_home.join("neovide"))
+}
+
+// Directory for files which only matter while Neovide runs, like sockets and the address of a
+// detached session.
+#[cfg(not(any(windows, target_os = "macos")))]
+pub fn runtime_directory() -> Option<PathBuf> {
+    match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(runtime_home) => Some(PathBuf::from(runtime_home).join("neovide")),
+        None => data_directory().map(|data_directory| data_directory.join("run")),
+    }
+}
+
+#[cfg(any(windows, target_os = "macos"))]
+pub fn runtime_directory() -> Option<PathBuf> {
+    data_directory().map(|data_directory| data_directory.join("run"))
+}
+
+// Creates the directory if needed and makes sure only the current user can get at it. Anything
+// Neovide reads back from such a directory could otherwise have been planted by another user, so a
+// directory owned by someone else or a symlink is refused.
+#[cfg(unix)]
+pub fn private_directory(path: &Path) -> io::Result<PathBuf> {
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(path)?;
+
+    let metadata = fs::symlink_metadata(path)?;
+    if !metadata.is_dir() {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("{:?} is not a directory", path),
+        ));
+    }
+    if metadata.uid() != unsafe { libc::getuid() } {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("{:?} is owned by another user", path),
+        ));
+    }
+    if metadata.mode() & 0o077 != 0 {
+        fs::set_permissions(path, fs::Permissions::from_mode(0o700))?;
+    }
+
+    Ok(path.to_path_buf())
+}
+
+// The directories used here are below the user's profile, which other users can't write to.
+#[cfg(windows)]
+pub fn private_directory(path: &Path) -> io::Result<PathBuf> {
+    fs::create_dir_all(path)?;
+    Ok(path.to_path_buf())
+}
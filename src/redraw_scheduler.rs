@@ -1,13 +1,17 @@
 use std::sync::atomic::{AtomicU16, Ordering};
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex, Weak};
 use std::time::Instant;
 
 use log::trace;
 
+use crate::clock::CLOCK;
+use crate::command_line;
 use crate::settings::*;
 
 lazy_static! {
-    pub static ref REDRAW_SCHEDULER: RedrawScheduler = RedrawScheduler::new();
+    // Every window owns its own scheduler. They are also tracked here so that process wide events
+    // such as recovered errors can wake all of them.
+    static ref SCHEDULERS: Mutex<Vec<Weak<RedrawScheduler>>> = Mutex::new(Vec::new());
 }
 
 #[derive(Clone)]
@@ -16,10 +20,7 @@ struct RedrawSettings {
 }
 
 pub fn initialize_settings() {
-    let buffer_frames = if SETTINGS
-        .neovim_arguments
-        .contains(&String::from("--extraBufferFrames"))
-    {
+    let buffer_frames = if command_line::has_argument("--extraBufferFrames") {
         60
     } else {
         1
@@ -35,13 +36,29 @@ pub fn initialize_settings() {
 pub struct RedrawScheduler {
     frames_queued: AtomicU16,
     scheduled_frame: Mutex<Option<Instant>>,
+    settings: Mutex<SettingsCache<RedrawSettings>>,
 }
 
 impl RedrawScheduler {
-    pub fn new() -> RedrawScheduler {
-        RedrawScheduler {
+    pub fn new() -> Arc<RedrawScheduler> {
+        let scheduler = Arc::new(RedrawScheduler {
             frames_queued: AtomicU16::new(1),
             scheduled_frame: Mutex::new(None),
+            settings: Mutex::new(SettingsCache::new()),
+        });
+
+        let mut schedulers = SCHEDULERS.lock().unwrap();
+        schedulers.retain(|scheduler| scheduler.strong_count() > 0);
+        schedulers.push(Arc::downgrade(&scheduler));
+
+        scheduler
+    }
+
+    // Runs the given function on the scheduler of every open window.
+    pub fn for_each<F: Fn(&RedrawScheduler)>(function: F) {
+        let schedulers = SCHEDULERS.lock().unwrap();
+        for scheduler in schedulers.iter().filter_map(Weak::upgrade) {
+            function(&scheduler);
         }
     }
 
@@ -60,7 +77,7 @@ impl RedrawScheduler {
 
     pub fn queue_next_frame(&self) {
         trace!("Next frame queued");
-        let buffer_frames = SETTINGS.get::<RedrawSettings>().extra_buffer_frames;
+        let buffer_frames = self.settings.lock().unwrap().get().extra_buffer_frames;
 
         self.frames_queued
             .store(buffer_frames as u16, Ordering::Relaxed);
@@ -77,7 +94,7 @@ impl RedrawScheduler {
             let mut next_scheduled_frame = self.scheduled_frame.lock().unwrap();
 
             if let Some(scheduled_frame) = *next_scheduled_frame {
-                if scheduled_frame < Instant::now() {
+                if scheduled_frame < CLOCK.now() {
                     *next_scheduled_frame = None;
                     true
                 } else {
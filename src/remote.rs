@@ -0,0 +1,440 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+use std::thread;
+use std::time::Duration;
+
+use log::{error, info, warn};
+use parking_lot::Mutex;
+
+use crate::bridge::{Bridge, UiCommand};
+use crate::command_line;
+use crate::paths::{private_directory, runtime_directory};
+
+// Single instance mode. The first Neovide started with --single-instance or one of the --remote
+// flags listens on a local socket only the same user can connect to. Later invocations with
+// --remote flags hand their files to it and exit instead of opening a window of their own.
+//
+// Requests are line based: the mode on the first line, then one absolute path per line, then an
+// empty line. The server answers "ok" once the files are queued and, for --remote-wait, "done"
+// once every buffer has been unloaded or "error: <message>" if the files couldn't be opened.
+const SINGLE_INSTANCE_FLAG: &str = "--single-instance";
+
+// How long a client gets to send its request, so that one which connects and then stalls doesn't
+// keep its connection's thread around.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+lazy_static! {
+    static ref RAISE_REQUESTED: AtomicBool = AtomicBool::new(false);
+    static ref TARGET: Mutex<Weak<Bridge>> = Mutex::new(Weak::new());
+    static ref NEXT_WAIT_ID: AtomicU64 = AtomicU64::new(0);
+    static ref PENDING_WAITS: Mutex<HashMap<u64, (Stream, usize)>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RemoteMode {
+    Open,
+    Tab,
+    Wait,
+}
+
+impl RemoteMode {
+    fn flag(self) -> &'static str {
+        match self {
+            RemoteMode::Open => "--remote",
+            RemoteMode::Tab => "--remote-tab",
+            RemoteMode::Wait => "--remote-wait",
+        }
+    }
+
+    fn from_flag(flag: &str) -> Option<RemoteMode> {
+        [RemoteMode::Open, RemoteMode::Tab, RemoteMode::Wait]
+            .iter()
+            .cloned()
+            .find(|mode| mode.flag() == flag)
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            RemoteMode::Open => "open",
+            RemoteMode::Tab => "tab",
+            RemoteMode::Wait => "wait",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<RemoteMode> {
+        [RemoteMode::Open, RemoteMode::Tab, RemoteMode::Wait]
+            .iter()
+            .cloned()
+            .find(|mode| mode.name() == name)
+    }
+}
+
+pub fn is_remote_argument(arg: &str) -> bool {
+    arg == SINGLE_INSTANCE_FLAG || RemoteMode::from_flag(arg).is_some()
+}
+
+// The remote mode and files passed on the command line. Files are made absolute since the
+// running instance may have a different working directory.
+fn remote_request() -> Option<(RemoteMode, Vec<String>)> {
+    let mode = command_line::arguments()
+        .iter()
+        .skip(1)
+        .find_map(|arg| RemoteMode::from_flag(arg))?;
+    let current_directory = std::env::current_dir().unwrap_or_default();
+
+    let mut files = Vec::new();
+    for arg in command_line::file_arguments() {
+        let file = current_directory.join(arg).to_string_lossy().into_owned();
+        if !files.contains(&file) {
+            files.push(file);
+        }
+    }
+
+    Some((mode, files))
+}
+
+fn should_listen() -> bool {
+    command_line::arguments()
+        .iter()
+        .any(|arg| is_remote_argument(arg))
+}
+
+fn private_runtime_directory() -> io::Result<PathBuf> {
+    let runtime_directory = runtime_directory().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            "Could not find a runtime directory",
+        )
+    })?;
+    private_directory(&runtime_directory)
+}
+
+// The socket lives in a directory only the current user can get into. Connections are still
+// checked against the user id in case the socket was handed to another user some other way.
+#[cfg(unix)]
+mod platform {
+    use std::io;
+    use std::os::unix::io::AsRawFd;
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::path::PathBuf;
+
+    pub type Listener = UnixListener;
+    pub type Stream = UnixStream;
+
+    fn socket_path() -> io::Result<PathBuf> {
+        Ok(super::private_runtime_directory()?.join("remote.sock"))
+    }
+
+    pub fn connect() -> io::Result<Stream> {
+        UnixStream::connect(socket_path()?)
+    }
+
+    // Only called once connecting failed, so any existing socket file was left behind by an
+    // instance which didn't shut down cleanly.
+    pub fn listen() -> io::Result<Listener> {
+        let socket_path = socket_path()?;
+        std::fs::remove_file(&socket_path).ok();
+        UnixListener::bind(socket_path)
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn peer_uid(stream: &Stream) -> io::Result<libc::uid_t> {
+        let mut credentials = libc::ucred {
+            pid: 0,
+            uid: 0,
+            gid: 0,
+        };
+        let mut length = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+        let result = unsafe {
+            libc::getsockopt(
+                stream.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_PEERCRED,
+                &mut credentials as *mut libc::ucred as *mut libc::c_void,
+                &mut length,
+            )
+        };
+        if result != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(credentials.uid)
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    fn peer_uid(stream: &Stream) -> io::Result<libc::uid_t> {
+        let mut uid = 0;
+        let mut gid = 0;
+        if unsafe { libc::getpeereid(stream.as_raw_fd(), &mut uid, &mut gid) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(uid)
+    }
+
+    pub fn authenticate(stream: &mut Stream) -> io::Result<()> {
+        if peer_uid(stream)? != unsafe { libc::getuid() } {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "Refused a remote request from another user",
+            ));
+        }
+        Ok(())
+    }
+}
+
+// Windows has no unix sockets here, so the server listens on a local tcp port. The port is
+// written to a file in the user's profile together with a random token which clients have to send
+// first, since any local user could connect to the port itself.
+#[cfg(not(unix))]
+mod platform {
+    use std::collections::hash_map::RandomState;
+    use std::hash::{BuildHasher, Hasher};
+    use std::io::{self, ErrorKind, Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::path::PathBuf;
+
+    pub type Listener = TcpListener;
+    pub type Stream = TcpStream;
+
+    lazy_static! {
+        static ref TOKEN: String = new_token();
+    }
+
+    // Every RandomState is seeded from the system's random number generator.
+    fn new_token() -> String {
+        (0..4)
+            .map(|_| format!("{:016x}", RandomState::new().build_hasher().finish()))
+            .collect()
+    }
+
+    fn port_path() -> io::Result<PathBuf> {
+        Ok(super::private_runtime_directory()?.join("remote.port"))
+    }
+
+    pub fn connect() -> io::Result<Stream> {
+        let contents = std::fs::read_to_string(port_path()?)?;
+        let mut lines = contents.lines();
+        let port = lines
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .parse::<u16>()
+            .map_err(|error| io::Error::new(ErrorKind::InvalidData, error))?;
+        let token = lines.next().unwrap_or_default().trim();
+
+        let mut stream = TcpStream::connect(("127.0.0.1", port))?;
+        stream.write_all(format!("{}\n", token).as_bytes())?;
+        Ok(stream)
+    }
+
+    pub fn listen() -> io::Result<Listener> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        std::fs::write(
+            port_path()?,
+            format!("{}\n{}\n", listener.local_addr()?.port(), *TOKEN),
+        )?;
+        Ok(listener)
+    }
+
+    // Reads exactly the token line so that the request after it is left in the stream.
+    pub fn authenticate(stream: &mut Stream) -> io::Result<()> {
+        let mut token = vec![0; TOKEN.len() + 1];
+        stream.read_exact(&mut token)?;
+        if token != format!("{}\n", *TOKEN).as_bytes() {
+            return Err(io::Error::new(
+                ErrorKind::PermissionDenied,
+                "Refused a remote request without the right token",
+            ));
+        }
+        Ok(())
+    }
+}
+
+use platform::Stream;
+
+fn send_request(mode: RemoteMode, files: &[String]) -> io::Result<()> {
+    let mut stream = platform::connect()?;
+
+    let mut request = format!("{}\n", mode.name());
+    for file in files {
+        request.push_str(file);
+        request.push('\n');
+    }
+    request.push('\n');
+    stream.write_all(request.as_bytes())?;
+
+    // For --remote-wait the connection stays open until the server reports that every buffer was
+    // closed, or until the server exits.
+    let expected_replies = if mode == RemoteMode::Wait { 2 } else { 1 };
+    let reader = BufReader::new(stream);
+    for line in reader.lines().take(expected_replies) {
+        let line = line?;
+        info!("Running instance replied {}", line);
+        if line.starts_with("error") {
+            eprintln!("{}", line);
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+// Returns true if the request was handed to an already running instance, in which case this
+// process has nothing left to do.
+pub fn forward_to_running_instance() -> bool {
+    let (mode, files) = match remote_request() {
+        Some(request) => request,
+        None => return false,
+    };
+
+    match send_request(mode, &files) {
+        Ok(()) => true,
+        Err(error) => {
+            info!(
+                "No running instance to forward to, starting a new one: {}",
+                error
+            );
+            false
+        }
+    }
+}
+
+fn parse_request(stream: &Stream) -> io::Result<(RemoteMode, Vec<String>)> {
+    let mut lines = BufReader::new(stream).lines();
+    let mode_name = lines.next().unwrap_or_else(|| Ok(String::new()))?;
+    let mode = RemoteMode::from_name(&mode_name).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Unknown remote mode {}", mode_name),
+        )
+    })?;
+
+    let mut files = Vec::new();
+    for line in lines {
+        let line = line?;
+        if line.is_empty() {
+            break;
+        }
+        files.push(line);
+    }
+
+    Ok((mode, files))
+}
+
+fn handle_connection(mut stream: Stream) -> io::Result<()> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    platform::authenticate(&mut stream)?;
+    let (mode, files) = parse_request(&stream)?;
+    info!("Remote request to {} {:?}", mode.name(), files);
+
+    let target = TARGET
+        .lock()
+        .upgrade()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotConnected, "No window to open files in"))?;
+
+    let wait_id = if mode == RemoteMode::Wait && !files.is_empty() {
+        let wait_id = NEXT_WAIT_ID.fetch_add(1, Ordering::Relaxed);
+        PENDING_WAITS
+            .lock()
+            .insert(wait_id, (stream.try_clone()?, files.len()));
+        Some(wait_id)
+    } else {
+        None
+    };
+
+    target.queue_command(UiCommand::OpenFiles {
+        files,
+        in_tabs: mode == RemoteMode::Tab,
+        wait_id,
+    });
+    RAISE_REQUESTED.store(true, Ordering::Relaxed);
+    target.redraw_scheduler.queue_next_frame();
+
+    stream.write_all(b"ok\n")?;
+    if mode == RemoteMode::Wait && wait_id.is_none() {
+        stream.write_all(b"done\n")?;
+    }
+    Ok(())
+}
+
+// Starts accepting remote requests if single instance mode was asked for.
+pub fn start_listening() {
+    if !should_listen() {
+        return;
+    }
+
+    let listener = match platform::listen() {
+        Ok(listener) => listener,
+        Err(error) => {
+            warn!("Could not listen for remote requests: {}", error);
+            return;
+        }
+    };
+
+    // Each connection is handled on its own thread so that a slow client can't hold up the
+    // requests behind it.
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    thread::spawn(move || {
+                        if let Err(error) = handle_connection(stream) {
+                            error!("Could not handle remote request: {}", error);
+                        }
+                    });
+                }
+                Err(error) => error!("Could not accept remote request: {}", error),
+            }
+        }
+    });
+}
+
+// Called when one of the buffers opened by --remote-wait is unloaded. Once all of them are, the
+// waiting client is released.
+pub fn buffer_closed(wait_id: u64) {
+    let mut pending_waits = PENDING_WAITS.lock();
+    let finished = match pending_waits.get_mut(&wait_id) {
+        Some((_, remaining)) => {
+            *remaining = remaining.saturating_sub(1);
+            *remaining == 0
+        }
+        None => false,
+    };
+
+    if finished {
+        if let Some((mut stream, _)) = pending_waits.remove(&wait_id) {
+            stream.write_all(b"done\n").ok();
+        }
+    }
+}
+
+// Called when the files of a --remote-wait request couldn't be opened. The waiting client is told
+// why instead of waiting for buffers which will never be unloaded.
+pub fn open_failed(wait_id: u64, message: &str) {
+    if let Some((mut stream, _)) = PENDING_WAITS.lock().remove(&wait_id) {
+        stream
+            .write_all(format!("error: {}\n", message).as_bytes())
+            .ok();
+    }
+}
+
+// Remote requests go to the most recently focused window.
+pub fn set_target(bridge: &Arc<Bridge>) {
+    *TARGET.lock() = Arc::downgrade(bridge);
+}
+
+pub fn is_target(bridge: &Arc<Bridge>) -> bool {
+    TARGET
+        .lock()
+        .upgrade()
+        .map(|target| Arc::ptr_eq(&target, bridge))
+        .unwrap_or(false)
+}
+
+// Remote requests should bring the target window to the front. The ui loop polls this every
+// frame.
+pub fn take_raise_request() -> bool {
+    RAISE_REQUESTED.swap(false, Ordering::Relaxed)
+}
@@ -10,7 +10,12 @@ use log::{trace, warn};
 use lru::LruCache;
 use skribo::{FontCollection, FontFamily, FontRef as SkriboFont, LayoutSession, TextStyle};
 use skulpin::skia_safe::{Data, Font as SkiaFont, TextBlob, TextBlobBuilder, Typeface};
+use std::cell::RefCell;
+use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::editor::FontOptions;
 
 const STANDARD_CHARACTER_STRING: &str =
     "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ1234567890";
@@ -40,6 +45,10 @@ const MISSING_GLYPH_FONT: &str = "Missing Glyphs.otf";
 struct Asset;
 
 const DEFAULT_FONT_SIZE: f32 = 14.0;
+const DEFAULT_FONT_WEIGHT: f32 = 400.0;
+// Fonts lighter than this are emboldened for bold text. The skew matches skia's own fake italics.
+const SYNTHETIC_BOLD_WEIGHT: f32 = 600.0;
+const SYNTHETIC_ITALIC_SKEW: f32 = -0.25;
 
 #[derive(Clone)]
 pub struct ExtendedFontFamily {
@@ -55,21 +64,23 @@ impl ExtendedFontFamily {
         self.fonts.push(font);
     }
 
+    // Picks the font of the requested style closest to the requested weight. Families without
+    // that style fall back to the closest weight of any style.
     pub fn get(&self, props: Properties) -> Option<&Font> {
-        for handle in &self.fonts {
-            let font = &handle.font;
-            let properties = font.properties();
-
-            if properties.weight == props.weight && properties.style == props.style {
-                return Some(&font);
-            }
-        }
-
-        if let Some(handle) = &self.fonts.first() {
-            return Some(&handle.font);
-        }
+        let weight_distance = |font: &Font| (font.properties().weight.0 - props.weight.0).abs();
+        let closest = |same_style: bool| {
+            self.fonts
+                .iter()
+                .map(|handle| &handle.font)
+                .filter(|font| !same_style || font.properties().style == props.style)
+                .min_by(|first, second| {
+                    weight_distance(first)
+                        .partial_cmp(&weight_distance(second))
+                        .unwrap_or(Ordering::Equal)
+                })
+        };
 
-        None
+        closest(true).or_else(|| closest(false))
     }
 
     pub fn from_normal_font_family(fonts: &[Handle]) -> ExtendedFontFamily {
@@ -124,6 +135,23 @@ impl FontLoader {
         self.get(font_name)
     }
 
+    // Makes a font available under the given name without going through the system font source.
+    #[cfg(feature = "golden-tests")]
+    pub fn register_font_data(&mut self, font_name: &str, font_data: Vec<u8>) -> bool {
+        match Font::from_bytes(font_data.into(), 0) {
+            Ok(font) => {
+                let mut family = ExtendedFontFamily::new();
+                family.add_font(SkriboFont::new(font));
+                self.cache.put(String::from(font_name), family);
+                true
+            }
+            Err(error) => {
+                warn!("Could not load font data for {}: {:?}", font_name, error);
+                false
+            }
+        }
+    }
+
     fn load(&mut self, font_name: &str) -> Option<ExtendedFontFamily> {
         let handle = match self.source.select_family_by_name(font_name) {
             Ok(it) => it,
@@ -154,33 +182,81 @@ struct ShapeKey {
     pub text: String,
     pub bold: bool,
     pub italic: bool,
+    pub double_width: bool,
+    pub font: String,
+    pub size_bits: u32,
+}
+
+// Loaded fonts and shaped text, shared between every window. Entries are keyed by font and size
+// so that windows using different fonts don't throw away each other's work.
+pub struct FontCaches {
+    loader: FontLoader,
+    font_cache: LruCache<(String, u32), SkiaFont>,
+    blob_cache: LruCache<ShapeKey, Vec<TextBlob>>,
 }
 
+impl FontCaches {
+    pub fn new() -> FontCaches {
+        FontCaches {
+            loader: FontLoader::new(),
+            font_cache: LruCache::new(10),
+            blob_cache: LruCache::new(10000),
+        }
+    }
+}
+
+pub type SharedFontCaches = Rc<RefCell<FontCaches>>;
+
+// Bold text is drawn at least at bold weight, even when guifont asks for a lighter one.
+fn font_properties(font_options: &FontOptions, bold: bool, italic: bool) -> Properties {
+    let mut weight = font_options.weight.unwrap_or(if font_options.bold {
+        Weight::BOLD.0
+    } else {
+        DEFAULT_FONT_WEIGHT
+    });
+    if bold {
+        weight = weight.max(Weight::BOLD.0);
+    }
+
+    let style = if italic || font_options.italic {
+        Style::Italic
+    } else {
+        Style::Normal
+    };
+
+    Properties {
+        weight: Weight(weight),
+        style,
+        stretch: Stretch::NORMAL,
+    }
+}
+
+// Every font in the guifont list that could be loaded takes part in the collection in order, so
+// later fonts fill in glyphs missing from earlier ones. The system default font is only used when
+// none of them could be loaded.
 pub fn build_collection_by_font_name(
     loader: &mut FontLoader,
-    font_name: Option<&str>,
+    font_options: &FontOptions,
     bold: bool,
     italic: bool,
 ) -> FontCollection {
     let mut collection = FontCollection::new();
+    let properties = font_properties(font_options, bold, italic);
+
+    let mut found_font = false;
+    for font_name in &font_options.font_list {
+        if let Some(family) = loader.get_or_load(font_name, false) {
+            if let Some(font) = family.get(properties) {
+                collection.add_family(FontFamily::new_from_font(font.clone()));
+                found_font = true;
+            }
+        }
+    }
 
-    if let Some(font_name) = font_name {
-        let weight = if bold { Weight::BOLD } else { Weight::NORMAL };
-        let style = if italic { Style::Italic } else { Style::Normal };
-        let properties = Properties {
-            weight,
-            style,
-            stretch: Stretch::NORMAL,
-        };
-
-        let gui_fonts = &[font_name, SYSTEM_DEFAULT_FONT];
-
-        for font_name in gui_fonts {
-            if let Some(family) = loader.get_or_load(font_name, false) {
-                if let Some(font) = family.get(properties) {
-                    collection.add_family(FontFamily::new_from_font(font.clone()));
-                    break;
-                }
+    if !found_font {
+        if let Some(family) = loader.get_or_load(SYSTEM_DEFAULT_FONT, false) {
+            if let Some(font) = family.get(properties) {
+                collection.add_family(FontFamily::new_from_font(font.clone()));
             }
         }
     }
@@ -200,37 +276,104 @@ pub fn build_collection_by_font_name(
     collection
 }
 
+fn missing_fonts(loader: &mut FontLoader, font_options: &FontOptions) -> Vec<String> {
+    font_options
+        .font_list
+        .iter()
+        .filter(|font_name| loader.get_or_load(font_name, false).is_none())
+        .cloned()
+        .collect()
+}
+
+// Fonts replacing the guifont fonts for one style, given in guifont syntax. Only the font list and
+// weight are taken from them, the size and width always come from guifont. Empty strings keep the
+// guifont fonts.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct StyleFonts {
+    pub bold: String,
+    pub italic: String,
+    pub bold_italic: String,
+}
+
+impl StyleFonts {
+    fn overrides(&self) -> Vec<FontOptions> {
+        [&self.bold, &self.italic, &self.bold_italic]
+            .iter()
+            .filter(|style_font| !style_font.is_empty())
+            .map(|style_font| FontOptions::parse(style_font))
+            .collect()
+    }
+}
+
+fn style_font_options(font_options: &FontOptions, style_font: &str) -> FontOptions {
+    if style_font.is_empty() {
+        return font_options.clone();
+    }
+
+    let style_options = FontOptions::parse(style_font);
+    FontOptions {
+        font_list: style_options.font_list,
+        weight: style_options.weight.or(font_options.weight),
+        ..font_options.clone()
+    }
+}
+
 struct FontSet {
     normal: FontCollection,
     bold: FontCollection,
     italic: FontCollection,
+    bold_italic: FontCollection,
 }
 
 impl FontSet {
-    fn new(font_name: Option<&str>, mut loader: &mut FontLoader) -> FontSet {
+    fn new(
+        font_options: &FontOptions,
+        style_fonts: &StyleFonts,
+        mut loader: &mut FontLoader,
+    ) -> FontSet {
+        let bold_options = style_font_options(font_options, &style_fonts.bold);
+        let italic_options = style_font_options(font_options, &style_fonts.italic);
+        let bold_italic_options = style_font_options(font_options, &style_fonts.bold_italic);
+
         FontSet {
-            normal: build_collection_by_font_name(&mut loader, font_name, false, false),
-            bold: build_collection_by_font_name(&mut loader, font_name, true, false),
-            italic: build_collection_by_font_name(&mut loader, font_name, false, true),
+            normal: build_collection_by_font_name(&mut loader, font_options, false, false),
+            bold: build_collection_by_font_name(&mut loader, &bold_options, true, false),
+            italic: build_collection_by_font_name(&mut loader, &italic_options, false, true),
+            bold_italic: build_collection_by_font_name(
+                &mut loader,
+                &bold_italic_options,
+                true,
+                true,
+            ),
         }
     }
 
     fn get(&self, bold: bool, italic: bool) -> &FontCollection {
         match (bold, italic) {
-            (true, _) => &self.bold,
             (false, false) => &self.normal,
+            (true, false) => &self.bold,
             (false, true) => &self.italic,
+            (true, true) => &self.bold_italic,
         }
     }
 }
 
 pub struct CachingShaper {
-    pub font_name: Option<String>,
+    pub font_options: FontOptions,
+    pub font_wide_options: FontOptions,
+    pub style_fonts: StyleFonts,
+    // Whether bold and italic text drawn with fonts lacking those faces is faked.
+    pub synthesize_styles: bool,
+    // Size requested by the font settings, and the size text is actually shaped at once zoom is
+    // applied.
+    pub font_size: f32,
+    pub zoom: f32,
     pub base_size: f32,
     font_set: FontSet,
-    font_loader: FontLoader,
-    font_cache: LruCache<String, SkiaFont>,
-    blob_cache: LruCache<ShapeKey, Vec<TextBlob>>,
+    // Used instead of the font set for double width graphemes when guifontwide names a font.
+    wide_font: Option<FontCollection>,
+    font_key: String,
+    caches: SharedFontCaches,
 }
 
 fn build_skia_font_from_skribo_font(skribo_font: &SkriboFont, base_size: f32) -> Option<SkiaFont> {
@@ -241,29 +384,65 @@ fn build_skia_font_from_skribo_font(skribo_font: &SkriboFont, base_size: f32) ->
     Some(SkiaFont::from_typeface(typeface, base_size))
 }
 
-impl CachingShaper {
-    pub fn new() -> CachingShaper {
-        let mut loader = FontLoader::new();
+// Fakes the requested style on fonts which lack it, by emboldening the glyph outlines for bold and
+// slanting them for italic. The blob keeps the adjusted font, so drawing needs no extra work.
+fn synthesize_style(skia_font: &mut SkiaFont, font: &Font, bold: bool, italic: bool) {
+    let properties = font.properties();
+    if bold && properties.weight.0 < SYNTHETIC_BOLD_WEIGHT {
+        skia_font.set_embolden(true);
+    }
+    if italic && properties.style == Style::Normal {
+        skia_font.set_skew_x(SYNTHETIC_ITALIC_SKEW);
+    }
+}
 
-        CachingShaper {
-            font_name: Some(String::from(SYSTEM_DEFAULT_FONT)),
+impl CachingShaper {
+    pub fn new(caches: SharedFontCaches) -> CachingShaper {
+        let font_options = FontOptions {
+            font_list: vec![String::from(SYSTEM_DEFAULT_FONT)],
+            ..FontOptions::default()
+        };
+        let style_fonts = StyleFonts::default();
+        let font_set = FontSet::new(&font_options, &style_fonts, &mut caches.borrow_mut().loader);
+
+        let mut shaper = CachingShaper {
+            font_key: String::new(),
+            font_options,
+            font_wide_options: FontOptions::default(),
+            style_fonts,
+            synthesize_styles: true,
+            font_size: DEFAULT_FONT_SIZE,
+            zoom: 1.0,
             base_size: DEFAULT_FONT_SIZE,
-            font_set: FontSet::new(Some(SYSTEM_DEFAULT_FONT), &mut loader),
-            font_loader: loader,
-            font_cache: LruCache::new(10),
-            blob_cache: LruCache::new(10000),
-        }
+            font_set,
+            wide_font: None,
+            caches,
+        };
+        shaper.update_font_key();
+        shaper
+    }
+
+    // Shaped text is cached by every font choice which could change its glyphs.
+    fn update_font_key(&mut self) {
+        self.font_key = format!(
+            "{};{};{:?};{}",
+            self.font_options, self.font_wide_options, self.style_fonts, self.synthesize_styles
+        );
     }
 
-    fn get_skia_font(&mut self, skribo_font: &SkriboFont) -> Option<&SkiaFont> {
-        let font_name = skribo_font.font.postscript_name()?;
+    fn get_skia_font(&self, skribo_font: &SkriboFont) -> Option<SkiaFont> {
+        let key = (
+            skribo_font.font.postscript_name()?,
+            self.base_size.to_bits(),
+        );
+        let mut caches = self.caches.borrow_mut();
 
-        if !self.font_cache.contains(&font_name) {
+        if !caches.font_cache.contains(&key) {
             let font = build_skia_font_from_skribo_font(skribo_font, self.base_size)?;
-            self.font_cache.put(font_name.clone(), font);
+            caches.font_cache.put(key.clone(), font);
         }
 
-        self.font_cache.get(&font_name)
+        caches.font_cache.get(&key).cloned()
     }
 
     fn metrics(&self) -> Metrics {
@@ -277,12 +456,22 @@ impl CachingShaper {
             .metrics()
     }
 
-    pub fn shape(&mut self, text: &str, bold: bool, italic: bool) -> Vec<TextBlob> {
+    pub fn shape(
+        &mut self,
+        text: &str,
+        bold: bool,
+        italic: bool,
+        double_width: bool,
+    ) -> Vec<TextBlob> {
         let style = TextStyle {
             size: self.base_size,
         };
 
-        let session = LayoutSession::create(text, &style, &self.font_set.get(bold, italic));
+        let collection = match &self.wide_font {
+            Some(wide_font) if double_width => wide_font,
+            _ => self.font_set.get(bold, italic),
+        };
+        let session = LayoutSession::create(text, &style, collection);
         let metrics = self.metrics();
         let ascent = metrics.ascent * self.base_size / metrics.units_per_em as f32;
         let mut blobs = Vec::new();
@@ -290,7 +479,11 @@ impl CachingShaper {
         for layout_run in session.iter_all() {
             let skribo_font = layout_run.font();
 
-            if let Some(skia_font) = self.get_skia_font(&skribo_font) {
+            if let Some(mut skia_font) = self.get_skia_font(&skribo_font) {
+                if self.synthesize_styles {
+                    synthesize_style(&mut skia_font, &skribo_font.font, bold, italic);
+                }
+
                 let mut blob_builder = TextBlobBuilder::new();
                 let count = layout_run.glyphs().count();
                 let (glyphs, positions) =
@@ -310,24 +503,103 @@ impl CachingShaper {
         blobs
     }
 
-    pub fn shape_cached(&mut self, text: &str, bold: bool, italic: bool) -> &Vec<TextBlob> {
-        let key = ShapeKey::new(text.to_string(), bold, italic);
-
-        if !self.blob_cache.contains(&key) {
-            let blobs = self.shape(text, bold, italic);
-            self.blob_cache.put(key.clone(), blobs);
+    pub fn shape_cached(
+        &mut self,
+        text: &str,
+        bold: bool,
+        italic: bool,
+        double_width: bool,
+    ) -> Vec<TextBlob> {
+        let key = ShapeKey::new(
+            text.to_string(),
+            bold,
+            italic,
+            double_width,
+            self.font_key.clone(),
+            self.base_size.to_bits(),
+        );
+
+        if let Some(blobs) = self.caches.borrow_mut().blob_cache.get(&key) {
+            return blobs.clone();
         }
 
-        self.blob_cache.get(&key).unwrap()
+        let blobs = self.shape(text, bold, italic, double_width);
+        self.caches.borrow_mut().blob_cache.put(key, blobs.clone());
+        blobs
+    }
+
+    #[cfg(feature = "golden-tests")]
+    pub fn register_font_data(&mut self, font_name: &str, font_data: Vec<u8>) -> bool {
+        self.caches
+            .borrow_mut()
+            .loader
+            .register_font_data(font_name, font_data)
+    }
+
+    // Font changes return the fonts which could not be found.
+    pub fn change_font(&mut self, font_options: &FontOptions) -> Vec<String> {
+        trace!("Font changed {:?}", font_options);
+        let mut caches = self.caches.borrow_mut();
+        let missing_fonts = missing_fonts(&mut caches.loader, font_options);
+
+        self.font_set = FontSet::new(font_options, &self.style_fonts, &mut caches.loader);
+        self.font_options = font_options.clone();
+        self.font_size = font_options.size.unwrap_or(DEFAULT_FONT_SIZE);
+        self.base_size = self.font_size * self.zoom;
+        drop(caches);
+        self.update_font_key();
+        missing_fonts
+    }
+
+    // Double width graphemes are shaped at the guifont size, so the size in guifontwide is
+    // ignored. Without any usable wide font they use the normal font set.
+    pub fn change_wide_font(&mut self, font_wide_options: &FontOptions) -> Vec<String> {
+        trace!("Wide font changed {:?}", font_wide_options);
+        let mut caches = self.caches.borrow_mut();
+        let missing_fonts = missing_fonts(&mut caches.loader, font_wide_options);
+
+        self.wide_font = if missing_fonts.len() < font_wide_options.font_list.len() {
+            Some(build_collection_by_font_name(
+                &mut caches.loader,
+                font_wide_options,
+                false,
+                false,
+            ))
+        } else {
+            None
+        };
+        self.font_wide_options = font_wide_options.clone();
+        drop(caches);
+        self.update_font_key();
+        missing_fonts
+    }
+
+    pub fn set_synthesize_styles(&mut self, synthesize_styles: bool) {
+        self.synthesize_styles = synthesize_styles;
+        self.update_font_key();
     }
 
-    pub fn change_font(&mut self, font_name: Option<&str>, base_size: Option<f32>) {
-        trace!("Font changed {:?} {:?}", &font_name, &base_size);
-        self.font_name = font_name.map(|name| name.to_string());
-        self.base_size = base_size.unwrap_or(DEFAULT_FONT_SIZE);
-        self.font_set = FontSet::new(font_name, &mut self.font_loader);
-        self.font_cache.clear();
-        self.blob_cache.clear();
+    pub fn change_style_fonts(&mut self, style_fonts: &StyleFonts) -> Vec<String> {
+        trace!("Style fonts changed {:?}", style_fonts);
+        let mut caches = self.caches.borrow_mut();
+        let missing_fonts = style_fonts
+            .overrides()
+            .iter()
+            .flat_map(|style_options| missing_fonts(&mut caches.loader, style_options))
+            .collect();
+
+        self.font_set = FontSet::new(&self.font_options, style_fonts, &mut caches.loader);
+        self.style_fonts = style_fonts.clone();
+        drop(caches);
+        self.update_font_key();
+        missing_fonts
+    }
+
+    // Shapes are cached by size, so zooming doesn't need to rebuild the font set.
+    pub fn set_zoom(&mut self, zoom: f32) {
+        trace!("Zoom changed {}", zoom);
+        self.zoom = zoom;
+        self.base_size = self.font_size * zoom;
     }
 
     pub fn font_base_dimensions(&mut self) -> (f32, f32) {
@@ -356,7 +628,10 @@ impl CachingShaper {
         }
 
         let (font_width, _) = amounts.into_iter().max_by_key(|(_, count)| *count).unwrap();
-        let font_width = font_width.parse::<f32>().unwrap();
+        let font_width = match self.font_options.width {
+            Some(width) => width * self.zoom,
+            None => font_width.parse::<f32>().unwrap(),
+        };
 
         (font_width, font_height)
     }
@@ -1,7 +1,9 @@
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
+use crate::clock::CLOCK;
 use crate::editor::Cursor;
-use crate::redraw_scheduler::REDRAW_SCHEDULER;
+use crate::redraw_scheduler::RedrawScheduler;
 
 pub enum BlinkState {
     Waiting,
@@ -13,21 +15,23 @@ pub struct BlinkStatus {
     state: BlinkState,
     last_transition: Instant,
     previous_cursor: Option<Cursor>,
+    redraw_scheduler: Arc<RedrawScheduler>,
 }
 
 impl BlinkStatus {
-    pub fn new() -> BlinkStatus {
+    pub fn new(redraw_scheduler: Arc<RedrawScheduler>) -> BlinkStatus {
         BlinkStatus {
             state: BlinkState::Waiting,
-            last_transition: Instant::now(),
+            last_transition: CLOCK.now(),
             previous_cursor: None,
+            redraw_scheduler,
         }
     }
 
     pub fn update_status(&mut self, new_cursor: &Cursor) -> bool {
         if self.previous_cursor.is_none() || new_cursor != self.previous_cursor.as_ref().unwrap() {
             self.previous_cursor = Some(new_cursor.clone());
-            self.last_transition = Instant::now();
+            self.last_transition = CLOCK.now();
             if new_cursor.blinkwait.is_some() && new_cursor.blinkwait != Some(0) {
                 self.state = BlinkState::Waiting;
             } else {
@@ -51,7 +55,7 @@ impl BlinkStatus {
         .map(Duration::from_millis);
 
         if delay
-            .map(|delay| self.last_transition + delay < Instant::now())
+            .map(|delay| self.last_transition + delay < CLOCK.now())
             .unwrap_or(false)
         {
             self.state = match self.state {
@@ -59,7 +63,7 @@ impl BlinkStatus {
                 BlinkState::On => BlinkState::Off,
                 BlinkState::Off => BlinkState::On,
             };
-            self.last_transition = Instant::now();
+            self.last_transition = CLOCK.now();
         }
 
         let scheduled_frame = (match self.state {
@@ -70,7 +74,7 @@ impl BlinkStatus {
         .map(|delay| self.last_transition + Duration::from_millis(delay));
 
         if let Some(scheduled_frame) = scheduled_frame {
-            REDRAW_SCHEDULER.schedule(scheduled_frame);
+            self.redraw_scheduler.schedule(scheduled_frame);
         }
 
         match self.state {
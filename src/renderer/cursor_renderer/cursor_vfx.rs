@@ -1,4 +1,3 @@
-use log::error;
 use skulpin::skia_safe::{paint::Style, BlendMode, Canvas, Color, Paint, Point, Rect};
 
 use super::animation_utils::*;
@@ -6,6 +5,8 @@ use super::CursorSettings;
 use crate::editor::{Colors, Cursor};
 use crate::settings::*;
 
+pub const DEFAULT_RNG_SEED: u64 = 0x853C49E6748FEA9Bu64;
+
 pub trait CursorVfx {
     fn update(
         &mut self,
@@ -47,30 +48,23 @@ pub enum VfxMode {
 }
 
 impl FromValue for VfxMode {
-    fn from_value(&mut self, value: Value) {
-        if value.is_str() {
-            *self = match value.as_str().unwrap() {
-                "sonicboom" => VfxMode::Highlight(HighlightMode::SonicBoom),
-                "ripple" => VfxMode::Highlight(HighlightMode::Ripple),
-                "wireframe" => VfxMode::Highlight(HighlightMode::Wireframe),
-                "railgun" => VfxMode::Trail(TrailMode::Railgun),
-                "torpedo" => VfxMode::Trail(TrailMode::Torpedo),
-                "pixiedust" => VfxMode::Trail(TrailMode::PixieDust),
-                "" => VfxMode::Disabled,
-                value => {
-                    error!("Expected a VfxMode name, but received {:?}", value);
-                    return;
-                }
-            };
-        } else {
-            error!("Expected a VfxMode string, but received {:?}", value);
+    fn from_value(value: Value) -> Result<Self, String> {
+        match value.as_str() {
+            Some("sonicboom") => Ok(VfxMode::Highlight(HighlightMode::SonicBoom)),
+            Some("ripple") => Ok(VfxMode::Highlight(HighlightMode::Ripple)),
+            Some("wireframe") => Ok(VfxMode::Highlight(HighlightMode::Wireframe)),
+            Some("railgun") => Ok(VfxMode::Trail(TrailMode::Railgun)),
+            Some("torpedo") => Ok(VfxMode::Trail(TrailMode::Torpedo)),
+            Some("pixiedust") => Ok(VfxMode::Trail(TrailMode::PixieDust)),
+            Some("") => Ok(VfxMode::Disabled),
+            _ => Err(format!("expected a VfxMode name, but received {}", value)),
         }
     }
 }
 
-impl From<VfxMode> for Value {
-    fn from(mode: VfxMode) -> Self {
-        match mode {
+impl IntoValue for VfxMode {
+    fn into_value(self) -> Value {
+        match self {
             VfxMode::Highlight(HighlightMode::SonicBoom) => Value::from("sonicboom"),
             VfxMode::Highlight(HighlightMode::Ripple) => Value::from("ripple"),
             VfxMode::Highlight(HighlightMode::Wireframe) => Value::from("wireframe"),
@@ -82,10 +76,10 @@ impl From<VfxMode> for Value {
     }
 }
 
-pub fn new_cursor_vfx(mode: &VfxMode) -> Option<Box<dyn CursorVfx>> {
+pub fn new_cursor_vfx(mode: &VfxMode, rng_seed: u64) -> Option<Box<dyn CursorVfx>> {
     match mode {
         VfxMode::Highlight(mode) => Some(Box::new(PointHighlight::new(mode))),
-        VfxMode::Trail(mode) => Some(Box::new(ParticleTrail::new(mode))),
+        VfxMode::Trail(mode) => Some(Box::new(ParticleTrail::new(mode, rng_seed))),
         VfxMode::Disabled => None,
     }
 }
@@ -188,12 +182,12 @@ pub struct ParticleTrail {
 }
 
 impl ParticleTrail {
-    pub fn new(trail_mode: &TrailMode) -> ParticleTrail {
+    pub fn new(trail_mode: &TrailMode, rng_seed: u64) -> ParticleTrail {
         ParticleTrail {
             particles: vec![],
             previous_cursor_dest: Point::new(0.0, 0.0),
             trail_mode: trail_mode.clone(),
-            rng: RngState::new(),
+            rng: RngState::new(rng_seed),
         }
     }
 
@@ -358,15 +352,16 @@ impl CursorVfx for ParticleTrail {
 }
 
 // Random number generator based on http://www.pcg-random.org/
+// The generator is seeded explicitly so that particle effects can be reproduced exactly.
 struct RngState {
     state: u64,
     inc: u64,
 }
 
 impl RngState {
-    fn new() -> RngState {
+    fn new(seed: u64) -> RngState {
         RngState {
-            state: 0x853C49E6748FEA9Bu64,
+            state: seed,
             inc: (0xDA3E39CB94B95BDBu64 << 1) | 1,
         }
     }
@@ -2,10 +2,13 @@ mod animation_utils;
 mod blink;
 mod cursor_vfx;
 
+use std::sync::Arc;
+
+use parking_lot::Mutex;
 use skulpin::skia_safe::{Canvas, Paint, Path, Point};
 
-use crate::editor::{Colors, Cursor, CursorShape, EDITOR};
-use crate::redraw_scheduler::REDRAW_SCHEDULER;
+use crate::editor::{Colors, Cursor, CursorShape, Editor};
+use crate::redraw_scheduler::RedrawScheduler;
 use crate::renderer::CachingShaper;
 use crate::settings::*;
 
@@ -186,24 +189,42 @@ pub struct CursorRenderer {
     previous_cursor_shape: Option<CursorShape>,
     cursor_vfx: Option<Box<dyn cursor_vfx::CursorVfx>>,
     previous_vfx_mode: cursor_vfx::VfxMode,
+    rng_seed: u64,
+    editor: Arc<Mutex<Editor>>,
+    redraw_scheduler: Arc<RedrawScheduler>,
+    settings: SettingsCache<CursorSettings>,
 }
 
 impl CursorRenderer {
-    pub fn new() -> CursorRenderer {
+    pub fn new(
+        editor: Arc<Mutex<Editor>>,
+        redraw_scheduler: Arc<RedrawScheduler>,
+    ) -> CursorRenderer {
         let mut renderer = CursorRenderer {
             corners: vec![Corner::new(); 4],
             previous_position: (0, 0),
             command_line_delay: 0,
-            blink_status: BlinkStatus::new(),
+            blink_status: BlinkStatus::new(redraw_scheduler.clone()),
             previous_cursor_shape: None,
             //cursor_vfx: Box::new(PointHighlight::new(Point{x:0.0, y:0.0}, HighlightMode::Ripple)),
             cursor_vfx: None,
             previous_vfx_mode: cursor_vfx::VfxMode::Disabled,
+            rng_seed: cursor_vfx::DEFAULT_RNG_SEED,
+            editor,
+            redraw_scheduler,
+            settings: SettingsCache::new(),
         };
         renderer.set_cursor_shape(&CursorShape::Block, DEFAULT_CELL_PERCENTAGE);
         renderer
     }
 
+    // Reseeds the particle effects. The current effect is rebuilt so that its output from this
+    // point on only depends on the seed and the frame deltas.
+    pub fn set_rng_seed(&mut self, rng_seed: u64) {
+        self.rng_seed = rng_seed;
+        self.cursor_vfx = cursor_vfx::new_cursor_vfx(&self.previous_vfx_mode, rng_seed);
+    }
+
     fn set_cursor_shape(&mut self, cursor_shape: &CursorShape, cell_percentage: f32) {
         self.corners = self
             .corners
@@ -245,10 +266,10 @@ impl CursorRenderer {
         dt: f32,
     ) {
         let render = self.blink_status.update_status(&cursor);
-        let settings = SETTINGS.get::<CursorSettings>();
+        let settings = self.settings.get().clone();
 
         if settings.vfx_mode != self.previous_vfx_mode {
-            self.cursor_vfx = cursor_vfx::new_cursor_vfx(&settings.vfx_mode);
+            self.cursor_vfx = cursor_vfx::new_cursor_vfx(&settings.vfx_mode, self.rng_seed);
             self.previous_vfx_mode = settings.vfx_mode.clone();
         }
 
@@ -256,7 +277,7 @@ impl CursorRenderer {
         paint.set_anti_alias(settings.antialiasing);
 
         self.previous_position = {
-            let editor = EDITOR.lock();
+            let editor = self.editor.lock();
             let (_, grid_y) = cursor.position;
             let (_, previous_y) = self.previous_position;
 
@@ -276,8 +297,8 @@ impl CursorRenderer {
         };
 
         let (grid_x, grid_y) = self.previous_position;
-        let (character, font_dimensions, in_insert_mode): (String, Point, bool) = {
-            let editor = EDITOR.lock();
+        let (character, is_double, font_dimensions, in_insert_mode): (String, bool, Point, bool) = {
+            let editor = self.editor.lock();
             let character = match editor.grid.get_cell(grid_x, grid_y) {
                 Some(Some((character, _))) => character.clone(),
                 _ => ' '.to_string(),
@@ -298,7 +319,12 @@ impl CursorRenderer {
                 _ => false,
             };
 
-            (character, (font_width, font_height).into(), in_insert_mode)
+            (
+                character,
+                is_double,
+                (font_width, font_height).into(),
+                in_insert_mode,
+            )
         };
 
         let destination: Point = (grid_x as f32 * font_width, grid_y as f32 * font_height).into();
@@ -342,7 +368,7 @@ impl CursorRenderer {
         }
 
         if animating || self.command_line_delay != 0 {
-            REDRAW_SCHEDULER.queue_next_frame();
+            self.redraw_scheduler.queue_next_frame();
         }
 
         if cursor.enabled && render {
@@ -367,7 +393,7 @@ impl CursorRenderer {
             canvas.save();
             canvas.clip_path(&path, None, Some(false));
 
-            let blobs = &shaper.shape_cached(&character, false, false);
+            let blobs = &shaper.shape_cached(&character, false, false, is_double);
 
             for blob in blobs.iter() {
                 canvas.draw_text_blob(&blob, destination, &paint);
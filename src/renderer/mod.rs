@@ -1,18 +1,71 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use log::trace;
+use parking_lot::Mutex;
 use skulpin::skia_safe::gpu::SurfaceOrigin;
-use skulpin::skia_safe::{colors, dash_path_effect, Budgeted, Canvas, Paint, Rect, Surface};
+use skulpin::skia_safe::{
+    colors, dash_path_effect, AlphaType, Budgeted, Canvas, ColorType, Data, EncodedImageFormat,
+    Image, ImageInfo, Paint, Rect, Surface,
+};
 use skulpin::CoordinateSystemHelper;
 
 mod caching_shaper;
 
 pub mod cursor_renderer;
-pub use caching_shaper::CachingShaper;
+pub use caching_shaper::{CachingShaper, FontCaches, SharedFontCaches, StyleFonts};
 
-use crate::editor::{Style, EDITOR};
+use crate::crash_report::CRASH_REPORTER;
+use crate::editor::{Editor, FontOptions, InlineImage, Style};
+use crate::error_handling::error_indicator_visible;
+use crate::redraw_scheduler::RedrawScheduler;
+use crate::settings::*;
 use cursor_renderer::CursorRenderer;
 
+// The zoom scales the guifont size without touching 'guifont', so a later guifont change keeps
+// the zoom level. Like every setting it is shared by all windows. The style fonts replace the
+// guifont fonts for bold, italic and bold italic text, and font synthesis fakes those styles for
+// fonts which don't have them.
+#[derive(Clone)]
+pub struct RendererSettings {
+    pub zoom: f32,
+    pub font_bold: String,
+    pub font_italic: String,
+    pub font_bold_italic: String,
+    pub font_synthesis: bool,
+}
+
+impl RendererSettings {
+    fn style_fonts(&self) -> StyleFonts {
+        StyleFonts {
+            bold: self.font_bold.clone(),
+            italic: self.font_italic.clone(),
+            bold_italic: self.font_bold_italic.clone(),
+        }
+    }
+}
+
+pub const MIN_ZOOM: f32 = 0.25;
+pub const MAX_ZOOM: f32 = 8.0;
+
+pub fn initialize_settings() {
+    SETTINGS.set(&RendererSettings {
+        zoom: 1.0,
+        font_bold: String::new(),
+        font_italic: String::new(),
+        font_bold_italic: String::new(),
+        font_synthesis: true,
+    });
+
+    register_nvim_setting!("zoom", RendererSettings::zoom, |zoom| {
+        in_range(zoom, MIN_ZOOM..=MAX_ZOOM)
+    });
+    register_nvim_setting!("font_bold", RendererSettings::font_bold);
+    register_nvim_setting!("font_italic", RendererSettings::font_italic);
+    register_nvim_setting!("font_bold_italic", RendererSettings::font_bold_italic);
+    register_nvim_setting!("font_synthesis", RendererSettings::font_synthesis);
+}
+
 pub struct Renderer {
     surface: Option<Surface>,
     paint: Paint,
@@ -21,18 +74,28 @@ pub struct Renderer {
     pub font_width: f32,
     pub font_height: f32,
     cursor_renderer: CursorRenderer,
+    editor: Arc<Mutex<Editor>>,
+    image_cache: HashMap<u64, (Arc<image::RgbaImage>, Image)>,
+    settings: SettingsCache<RendererSettings>,
+    font_error: Option<String>,
 }
 
 impl Renderer {
-    pub fn new() -> Renderer {
+    // Draws the given editor. Font loading and shaping results are shared with every other
+    // renderer using the same caches.
+    pub fn new(
+        editor: Arc<Mutex<Editor>>,
+        redraw_scheduler: Arc<RedrawScheduler>,
+        font_caches: SharedFontCaches,
+    ) -> Renderer {
         let surface = None;
         let mut paint = Paint::new(colors::WHITE, None);
         paint.set_anti_alias(false);
 
-        let mut shaper = CachingShaper::new();
+        let mut shaper = CachingShaper::new(font_caches);
 
         let (font_width, font_height) = shaper.font_base_dimensions();
-        let cursor_renderer = CursorRenderer::new();
+        let cursor_renderer = CursorRenderer::new(editor.clone(), redraw_scheduler);
 
         Renderer {
             surface,
@@ -41,16 +104,91 @@ impl Renderer {
             font_width,
             font_height,
             cursor_renderer,
+            editor,
+            image_cache: HashMap::new(),
+            settings: SettingsCache::new(),
+            font_error: None,
+        }
+    }
+
+    #[cfg(feature = "golden-tests")]
+    pub fn register_font_data(&mut self, font_name: &str, font_data: Vec<u8>) -> bool {
+        self.shaper.register_font_data(font_name, font_data)
+    }
+
+    pub fn set_font(&mut self, font_options: &FontOptions) {
+        let missing_fonts = self.shaper.change_font(font_options);
+        self.report_missing_fonts(missing_fonts);
+        let (font_width, font_height) = self.shaper.font_base_dimensions();
+        self.font_width = font_width;
+        self.font_height = font_height;
+
+        CRASH_REPORTER.set_gui_state(
+            "font",
+            format!(
+                "{} size {} cell {}x{}",
+                font_options, self.shaper.base_size, font_width, font_height
+            ),
+        );
+    }
+
+    pub fn set_style_fonts(&mut self, style_fonts: &StyleFonts) {
+        let missing_fonts = self.shaper.change_style_fonts(style_fonts);
+        self.report_missing_fonts(missing_fonts);
+    }
+
+    pub fn set_wide_font(&mut self, font_wide_options: &FontOptions) {
+        let missing_fonts = self.shaper.change_wide_font(font_wide_options);
+        self.report_missing_fonts(missing_fonts);
+    }
+
+    fn report_missing_fonts(&mut self, missing_fonts: Vec<String>) {
+        if !missing_fonts.is_empty() {
+            self.font_error = Some(format!(
+                "E596: Invalid font(s): {}",
+                missing_fonts.join(", ")
+            ));
         }
     }
 
-    fn set_font(&mut self, name: Option<&str>, size: Option<f32>) {
-        self.shaper.change_font(name, size);
+    // The error to show in nvim for fonts the last font change couldn't find, if there were any.
+    pub fn take_font_error(&mut self) -> Option<String> {
+        self.font_error.take()
+    }
+
+    // Scales the font without changing the font settings. The cell size changes, so the grid size
+    // has to be recomputed afterwards.
+    fn set_zoom(&mut self, zoom: f32) {
+        self.shaper.set_zoom(zoom);
         let (font_width, font_height) = self.shaper.font_base_dimensions();
         self.font_width = font_width;
         self.font_height = font_height;
     }
 
+    pub fn set_particle_seed(&mut self, seed: u64) {
+        self.cursor_renderer.set_rng_seed(seed);
+    }
+
+    pub fn zoom(&self) -> f32 {
+        self.shaper.zoom
+    }
+
+    pub fn font_size(&self) -> f32 {
+        self.shaper.font_size
+    }
+
+    pub fn font_name(&self) -> Option<&str> {
+        self.shaper.font_options.primary_font()
+    }
+
+    // Encodes the most recently drawn grid contents as a png. The cursor is drawn separately and
+    // isn't included.
+    pub fn screenshot(&mut self) -> Option<Vec<u8>> {
+        let image = self.surface.as_mut()?.image_snapshot();
+        let data = image.encode_to_data(EncodedImageFormat::PNG)?;
+        Some(data.as_bytes().to_vec())
+    }
+
     fn compute_text_region(&self, grid_pos: (u64, u64), cell_width: u64) -> Rect {
         let (grid_x, grid_y) = grid_pos;
         let x = grid_x as f32 * self.font_width;
@@ -82,6 +220,7 @@ impl Renderer {
         text: &str,
         grid_pos: (u64, u64),
         cell_width: u64,
+        double_width: bool,
         style: &Option<Arc<Style>>,
         default_style: &Arc<Style>,
     ) {
@@ -127,7 +266,7 @@ impl Renderer {
         if !text.is_empty() {
             for blob in self
                 .shaper
-                .shape_cached(text, style.bold, style.italic)
+                .shape_cached(text, style.bold, style.italic, double_width)
                 .iter()
             {
                 canvas.draw_text_blob(blob, (x, y), &self.paint);
@@ -144,39 +283,186 @@ impl Renderer {
         canvas.restore();
     }
 
+    // Skia copies of the decoded pixels are kept per image id until the image is replaced.
+    fn skia_image(&mut self, image: &InlineImage, id: u64) -> Option<Image> {
+        if let Some((pixels, skia_image)) = self.image_cache.get(&id) {
+            if Arc::ptr_eq(pixels, &image.pixels) {
+                return Some(skia_image.clone());
+            }
+        }
+
+        let (width, height) = image.pixels.dimensions();
+        let image_info = ImageInfo::new(
+            (width as i32, height as i32),
+            ColorType::RGBA8888,
+            AlphaType::Unpremul,
+            None,
+        );
+        let data = Data::new_copy(image.pixels.as_raw());
+        let skia_image = Image::from_raster_data(&image_info, data, width as usize * 4)?;
+
+        self.image_cache
+            .insert(id, (image.pixels.clone(), skia_image.clone()));
+        Some(skia_image)
+    }
+
+    // Images are drawn above the text but below the cursor, clipped to the grid so that scrolled
+    // images don't spill into the window padding.
+    fn draw_images(
+        &mut self,
+        canvas: &mut Canvas,
+        images: &HashMap<u64, InlineImage>,
+        grid_size: (u64, u64),
+    ) {
+        self.image_cache.retain(|id, _| images.contains_key(id));
+        if images.is_empty() {
+            return;
+        }
+
+        let (grid_width, grid_height) = grid_size;
+        let grid_region = Rect::new(
+            0.0,
+            0.0,
+            grid_width as f32 * self.font_width,
+            grid_height as f32 * self.font_height,
+        );
+        let paint = Paint::default();
+
+        canvas.save();
+        canvas.clip_rect(grid_region, None, Some(false));
+        for (id, image) in images.iter() {
+            if let Some(skia_image) = self.skia_image(image, *id) {
+                let destination = Rect::from_xywh(
+                    image.column as f32 * self.font_width,
+                    image.row as f32 * self.font_height,
+                    image.width as f32 * self.font_width,
+                    image.height as f32 * self.font_height,
+                );
+                canvas.draw_image_rect(skia_image, None, &destination, &paint);
+            }
+        }
+        canvas.restore();
+    }
+
+    // Marks the top right corner of the window while a recently recovered error is still relevant.
+    fn draw_error_indicator(&self, canvas: &mut Canvas, logical_width: f32) {
+        let radius = self.font_height / 4.0;
+        let mut paint = Paint::new(colors::RED, None);
+        paint.set_anti_alias(true);
+
+        canvas.draw_circle((logical_width - radius * 2.0, radius * 2.0), radius, &paint);
+    }
+
+    // Replaces the editor contents with an error message. Used once nvim is gone and the message
+    // is all that is left to show.
+    pub fn draw_error_overlay(
+        &mut self,
+        gpu_canvas: &mut Canvas,
+        coordinate_system_helper: &CoordinateSystemHelper,
+        message: &str,
+    ) {
+        coordinate_system_helper.use_logical_coordinates(gpu_canvas);
+        gpu_canvas.clear(colors::BLACK.to_color());
+
+        let lines = message
+            .lines()
+            .chain(["", "Press any key to close Neovide."].iter().cloned());
+
+        self.paint.set_color(colors::WHITE.to_color());
+        for (index, line) in lines.enumerate() {
+            let line = line.trim_end();
+            if line.is_empty() {
+                continue;
+            }
+
+            let position = (self.font_width, (index + 1) as f32 * self.font_height);
+            for blob in self.shaper.shape_cached(line, false, false, false).iter() {
+                gpu_canvas.draw_text_blob(blob, position, &self.paint);
+            }
+        }
+    }
+
     pub fn draw(
         &mut self,
         gpu_canvas: &mut Canvas,
         coordinate_system_helper: &CoordinateSystemHelper,
         dt: f32,
+    ) -> bool {
+        let scale_factor = coordinate_system_helper.scale_factor() as f32;
+        let window_size = coordinate_system_helper.window_logical_size();
+        let logical_size = (window_size.width as f32, window_size.height as f32);
+
+        self.draw_to_canvas(gpu_canvas, scale_factor, logical_size, dt)
+    }
+
+    // Draws the editor state onto any canvas. Gpu backed canvases get a gpu backed text surface,
+    // everything else (such as offscreen raster canvases) gets a raster one.
+    pub fn draw_to_canvas(
+        &mut self,
+        root_canvas: &mut Canvas,
+        scale_factor: f32,
+        logical_size: (f32, f32),
+        dt: f32,
     ) -> bool {
         trace!("Rendering");
 
-        let ((draw_commands, should_clear), default_style, cursor, font_name, font_size) = {
-            let mut editor = EDITOR.lock();
+        // The cell size doesn't depend on the style fonts or font synthesis, so the grid is
+        // redrawn here instead of after a resize.
+        let (style_fonts, font_synthesis) = {
+            let settings = self.settings.get();
+            (settings.style_fonts(), settings.font_synthesis)
+        };
+        let style_fonts_changed = style_fonts != self.shaper.style_fonts;
+        if style_fonts_changed {
+            self.set_style_fonts(&style_fonts);
+        }
+        let font_synthesis_changed = font_synthesis != self.shaper.synthesize_styles;
+        if font_synthesis_changed {
+            self.shaper.set_synthesize_styles(font_synthesis);
+        }
+        if style_fonts_changed || font_synthesis_changed {
+            self.editor.lock().grid.set_dirty_all(true);
+        }
+
+        let (
+            (draw_commands, should_clear),
+            default_style,
+            cursor,
+            font_options,
+            font_wide_options,
+            images,
+            grid_size,
+        ) = {
+            let mut editor = self.editor.lock();
             (
                 editor.build_draw_commands(),
                 editor.default_style.clone(),
                 editor.cursor.clone(),
-                editor.font_name.clone(),
-                editor.font_size,
+                editor.font_options.clone(),
+                editor.font_wide_options.clone(),
+                editor.images.clone(),
+                (editor.grid.width, editor.grid.height),
             )
         };
 
-        let current_font = Some(self.shaper.font_name.clone().unwrap_or(String::from("")));
-        let editor_font = if font_name.clone().unwrap_or_default().is_empty() {
-            &current_font
-        } else {
-            &font_name
+        let font_changed = match font_options {
+            Some(font_options) if font_options != self.shaper.font_options => {
+                self.set_font(&font_options);
+                true
+            }
+            _ => false,
         };
 
-        let font_changed = current_font != *editor_font
-            || font_size
-                .map(|new_size| (new_size - self.shaper.base_size).abs() > std::f32::EPSILON)
-                .unwrap_or(false);
+        if let Some(font_wide_options) = font_wide_options {
+            if font_wide_options != self.shaper.font_wide_options {
+                self.set_wide_font(&font_wide_options);
+            }
+        }
 
-        if font_changed {
-            self.set_font(font_name.as_deref(), font_size);
+        let zoom = self.settings.get().zoom;
+        let zoom_changed = (zoom - self.shaper.zoom).abs() > std::f32::EPSILON;
+        if zoom_changed {
+            self.set_zoom(zoom);
         }
 
         if should_clear {
@@ -184,31 +470,32 @@ impl Renderer {
         }
 
         let mut surface = self.surface.take().unwrap_or_else(|| {
-            let mut context = gpu_canvas.gpu_context().unwrap();
-            let budgeted = Budgeted::YES;
-            let image_info = gpu_canvas.image_info();
-            let surface_origin = SurfaceOrigin::TopLeft;
-            let mut surface = Surface::new_render_target(
-                &mut context,
-                budgeted,
-                &image_info,
-                None,
-                surface_origin,
-                None,
-                None,
-            )
+            let image_info = root_canvas.image_info();
+            let mut surface = match root_canvas.gpu_context() {
+                Some(mut context) => Surface::new_render_target(
+                    &mut context,
+                    Budgeted::YES,
+                    &image_info,
+                    None,
+                    SurfaceOrigin::TopLeft,
+                    None,
+                    None,
+                ),
+                None => Surface::new_raster(&image_info, None, None),
+            }
             .expect("Could not create surface");
             let canvas = surface.canvas();
             canvas.clear(default_style.colors.background.clone().unwrap().to_color());
             surface
         });
 
-        let mut canvas = surface.canvas();
-        coordinate_system_helper.use_logical_coordinates(&mut canvas);
+        let canvas = surface.canvas();
+        canvas.reset_matrix();
+        canvas.scale((scale_factor, scale_factor));
 
         for command in draw_commands.iter() {
             self.draw_background(
-                &mut canvas,
+                canvas,
                 command.grid_position.clone(),
                 command.cell_width,
                 &command.style,
@@ -218,25 +505,22 @@ impl Renderer {
 
         for command in draw_commands.iter() {
             self.draw_foreground(
-                &mut canvas,
+                canvas,
                 &command.text,
                 command.grid_position.clone(),
                 command.cell_width,
+                command.double_width,
                 &command.style,
                 &default_style,
             );
         }
 
         let image = surface.image_snapshot();
-        let window_size = coordinate_system_helper.window_logical_size();
-        let image_destination = Rect::new(
-            0.0,
-            0.0,
-            window_size.width as f32,
-            window_size.height as f32,
-        );
+        let (logical_width, logical_height) = logical_size;
+        let image_destination = Rect::new(0.0, 0.0, logical_width, logical_height);
 
-        gpu_canvas.draw_image_rect(image, None, &image_destination, &self.paint);
+        root_canvas.draw_image_rect(image, None, &image_destination, &self.paint);
+        self.draw_images(root_canvas, &images, grid_size);
 
         self.surface = Some(surface);
         self.cursor_renderer.draw(
@@ -245,10 +529,14 @@ impl Renderer {
             self.font_width,
             self.font_height,
             &mut self.shaper,
-            gpu_canvas,
+            root_canvas,
             dt,
         );
 
-        font_changed
+        if error_indicator_visible() {
+            self.draw_error_indicator(root_canvas, logical_width);
+        }
+
+        font_changed || zoom_changed
     }
 }
@@ -0,0 +1,258 @@
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::fmt::{self, Display};
+use std::ops::RangeInclusive;
+
+use rmpv::Value;
+use skulpin::skia_safe::Color as SkiaColor;
+
+// Trait to allow for conversion from rmpv::Value to any other data type.
+// Note: Feel free to implement this trait for custom types in each subsystem.
+// Errors describe what was expected and are shown to the user in nvim.
+pub trait FromValue: Sized {
+    fn from_value(value: Value) -> Result<Self, String>;
+}
+
+// The reverse conversion, used to write defaults and rejected values back to nvim. This is a
+// separate trait rather than `From<MyType> for Value` since the orphan rule doesn't allow the
+// generic impls below for rmpv's Value.
+pub trait IntoValue {
+    fn into_value(self) -> Value;
+}
+
+macro_rules! into_value_through_from {
+    ($($type_name: ty),*) => {
+        $(
+            impl IntoValue for $type_name {
+                fn into_value(self) -> Value {
+                    Value::from(self)
+                }
+            }
+        )*
+    };
+}
+
+into_value_through_from!(f32, u64, u32, i32, String, bool);
+
+// FromValue implementations for most typical types
+impl FromValue for f32 {
+    fn from_value(value: Value) -> Result<Self, String> {
+        if value.is_f64() {
+            Ok(value.as_f64().unwrap() as f32)
+        } else if value.is_i64() {
+            Ok(value.as_i64().unwrap() as f32)
+        } else if value.is_u64() {
+            Ok(value.as_u64().unwrap() as f32)
+        } else {
+            Err(format!("expected a number, but received {}", value))
+        }
+    }
+}
+
+impl FromValue for u64 {
+    fn from_value(value: Value) -> Result<Self, String> {
+        value
+            .as_u64()
+            .ok_or_else(|| format!("expected a positive integer, but received {}", value))
+    }
+}
+
+impl FromValue for u32 {
+    fn from_value(value: Value) -> Result<Self, String> {
+        value
+            .as_u64()
+            .and_then(|number| number.try_into().ok())
+            .ok_or_else(|| format!("expected a positive integer, but received {}", value))
+    }
+}
+
+impl FromValue for i32 {
+    fn from_value(value: Value) -> Result<Self, String> {
+        value
+            .as_i64()
+            .and_then(|number| number.try_into().ok())
+            .ok_or_else(|| format!("expected an integer, but received {}", value))
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: Value) -> Result<Self, String> {
+        value
+            .as_str()
+            .map(String::from)
+            .ok_or_else(|| format!("expected a string, but received {}", value))
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: Value) -> Result<Self, String> {
+        if value.is_bool() {
+            Ok(value.as_bool().unwrap())
+        } else if value.is_u64() {
+            Ok(value.as_u64().unwrap() != 0)
+        } else {
+            Err(format!("expected a boolean, but received {}", value))
+        }
+    }
+}
+
+// Constraints for register_nvim_setting. They are checked after the value was converted, and
+// failing them rejects the value the same way a type mismatch does.
+pub fn in_range<T: PartialOrd + Display>(
+    value: &T,
+    range: RangeInclusive<T>,
+) -> Result<(), String> {
+    if range.contains(value) {
+        Ok(())
+    } else {
+        Err(format!(
+            "expected a value between {} and {}, but received {}",
+            range.start(),
+            range.end(),
+            value
+        ))
+    }
+}
+
+pub fn greater_than<T: PartialOrd + Display>(value: &T, bound: T) -> Result<(), String> {
+    if *value > bound {
+        Ok(())
+    } else {
+        Err(format!(
+            "expected a value greater than {}, but received {}",
+            bound, value
+        ))
+    }
+}
+
+// Unset options are written as v:null, which is also how users clear them.
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(value: Value) -> Result<Self, String> {
+        match value {
+            Value::Nil => Ok(None),
+            value => T::from_value(value).map(Some),
+        }
+    }
+}
+
+impl<T: IntoValue> IntoValue for Option<T> {
+    fn into_value(self) -> Value {
+        self.map(IntoValue::into_value).unwrap_or(Value::Nil)
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(value: Value) -> Result<Self, String> {
+        match value {
+            Value::Array(items) => items
+                .into_iter()
+                .enumerate()
+                .map(|(index, item)| {
+                    T::from_value(item).map_err(|error| format!("item {}: {}", index + 1, error))
+                })
+                .collect(),
+            value => Err(format!("expected a list, but received {}", value)),
+        }
+    }
+}
+
+impl<T: IntoValue> IntoValue for Vec<T> {
+    fn into_value(self) -> Value {
+        Value::Array(self.into_iter().map(IntoValue::into_value).collect())
+    }
+}
+
+impl<T: FromValue> FromValue for HashMap<String, T> {
+    fn from_value(value: Value) -> Result<Self, String> {
+        match value {
+            Value::Map(entries) => entries
+                .into_iter()
+                .map(|(key, value)| {
+                    let key = String::from_value(key).map_err(|error| format!("key {}", error))?;
+                    let value =
+                        T::from_value(value).map_err(|error| format!("{}: {}", key, error))?;
+                    Ok((key, value))
+                })
+                .collect(),
+            value => Err(format!("expected a dictionary, but received {}", value)),
+        }
+    }
+}
+
+impl<T: IntoValue> IntoValue for HashMap<String, T> {
+    fn into_value(self) -> Value {
+        let mut entries = self
+            .into_iter()
+            .map(|(key, value)| (Value::from(key), value.into_value()))
+            .collect::<Vec<(Value, Value)>>();
+        entries.sort_by(|(a, _), (b, _)| a.as_str().cmp(&b.as_str()));
+        Value::Map(entries)
+    }
+}
+
+// A color written as "#rrggbb" or "#rrggbbaa" in nvim.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Color {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+    pub alpha: u8,
+}
+
+impl Color {
+    pub fn parse(text: &str) -> Option<Color> {
+        if !text.starts_with('#') || !text.is_ascii() {
+            return None;
+        }
+
+        let digits = &text[1..];
+        if digits.len() != 6 && digits.len() != 8 {
+            return None;
+        }
+
+        let channel = |index: usize| u8::from_str_radix(&digits[index * 2..index * 2 + 2], 16);
+        Some(Color {
+            red: channel(0).ok()?,
+            green: channel(1).ok()?,
+            blue: channel(2).ok()?,
+            alpha: if digits.len() == 8 {
+                channel(3).ok()?
+            } else {
+                255
+            },
+        })
+    }
+
+    pub fn to_skia(self) -> SkiaColor {
+        SkiaColor::from_argb(self.alpha, self.red, self.green, self.blue)
+    }
+}
+
+impl Display for Color {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "#{:02x}{:02x}{:02x}",
+            self.red, self.green, self.blue
+        )?;
+        if self.alpha != 255 {
+            write!(formatter, "{:02x}", self.alpha)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromValue for Color {
+    fn from_value(value: Value) -> Result<Self, String> {
+        value
+            .as_str()
+            .and_then(Color::parse)
+            .ok_or_else(|| format!("expected a color like \"#rrggbb\", but received {}", value))
+    }
+}
+
+impl IntoValue for Color {
+    fn into_value(self) -> Value {
+        Value::from(self.to_string())
+    }
+}
@@ -0,0 +1,684 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+
+use log::{error, warn};
+use nvim_rs::Neovim;
+use parking_lot::RwLock;
+pub use rmpv::Value;
+
+use crate::bridge::{Bridge, NeovimWriter, UiCommand};
+use crate::error_handling::report_recoverable_error;
+
+mod from_value;
+
+pub use from_value::*;
+pub use neovide_derive::SettingEnum;
+
+lazy_static! {
+    pub static ref SETTINGS: Settings = Settings::new();
+}
+
+// Macro to register settings changed handlers.
+// Note: Invocations to this macro must happen before the call to Settings::read_initial_values.
+#[macro_export]
+macro_rules! register_nvim_setting {
+    ($vim_setting_name: expr, $type_name:ident :: $field_name: ident) => {{
+        register_nvim_setting!($vim_setting_name, $type_name::$field_name, |_| Ok(()))
+    }};
+    ($vim_setting_name: expr, $type_name:ident :: $field_name: ident, $constraint: expr) => {{
+        // The update func sets a new value for a setting. Rejected values leave the setting
+        // untouched.
+        fn update_func(value: Value) -> Result<(), String> {
+            let mut s = SETTINGS.get::<$type_name>();
+            let new_value = $crate::settings::FromValue::from_value(value)?;
+            let constraint: fn(&_) -> Result<(), String> = $constraint;
+            constraint(&new_value)?;
+            s.$field_name = new_value;
+            SETTINGS.set(&s);
+            Ok(())
+        }
+
+        // The reader func retrieves the current value for a setting
+        fn reader_func() -> Value {
+            let s = SETTINGS.get::<$type_name>();
+            $crate::settings::IntoValue::into_value(s.$field_name)
+        }
+
+        let type_name = {
+            let s = SETTINGS.get::<$type_name>();
+            $crate::settings::type_name_of(&s.$field_name)
+        };
+
+        SETTINGS.set_setting_handlers($vim_setting_name, update_func, reader_func, type_name);
+    }};
+}
+
+// Function types to handle settings updates
+type UpdateHandlerFunc = fn(Value) -> Result<(), String>;
+type ReaderFunc = fn() -> Value;
+
+pub fn type_name_of<T>(_: &T) -> &'static str {
+    std::any::type_name::<T>()
+}
+
+// Strips module paths from a type name, so that `core::option::Option<alloc::string::String>`
+// reads as `Option<String>`.
+fn short_type_name(type_name: &str) -> String {
+    let mut short_name = String::new();
+    let mut segment = String::new();
+    for character in type_name.chars().chain(std::iter::once(' ')) {
+        if character.is_alphanumeric() || character == '_' || character == ':' {
+            segment.push(character);
+        } else {
+            short_name.push_str(segment.rsplit("::").next().unwrap_or_default());
+            short_name.push(character);
+            segment.clear();
+        }
+    }
+    short_name.trim_end().to_string()
+}
+
+// Where a setting value came from. Only flat g:neovide_ variables have a variable of their own to
+// reset when the value is rejected.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SettingSource {
+    Global,
+    Nested,
+    Override,
+}
+
+// A value nvim sent for a setting which couldn't be accepted.
+#[derive(new, Debug)]
+pub struct SettingError {
+    pub name: String,
+    pub source: SettingSource,
+    pub message: String,
+}
+
+impl SettingError {
+    fn variable(&self) -> String {
+        match self.source {
+            SettingSource::Global => format!("g:neovide_{}", self.name),
+            SettingSource::Nested => format!("g:neovide {}", self.name),
+            SettingSource::Override => format!("b:neovide_{0} or w:neovide_{0}", self.name),
+        }
+    }
+}
+
+// A buffer or window scoped value replacing a setting while the cursor is in that buffer or
+// window. The global value is kept so it can be restored on leaving.
+#[derive(new, Debug, Clone)]
+struct SettingOverride {
+    global: Value,
+    value: Value,
+}
+
+// Flattens g:neovide = { cursor = { vfx_mode = 'railgun' } } into ("cursor_vfx_mode", "railgun").
+// A key path naming a registered setting is taken as it is, so dictionary settings can be nested
+// as well.
+fn flatten_nested_settings(
+    prefix: Option<&str>,
+    value: Value,
+    listeners: &HashMap<String, UpdateHandlerFunc>,
+    settings: &mut Vec<(String, Value)>,
+) {
+    if let Some(prefix) = prefix {
+        if listeners.contains_key(prefix) {
+            settings.push((String::from(prefix), value));
+            return;
+        }
+    }
+
+    match value {
+        Value::Map(entries) => {
+            for (key, value) in entries {
+                let key = match key.as_str() {
+                    Some(key) => key,
+                    None => continue,
+                };
+                let name = match prefix {
+                    Some(prefix) => format!("{}_{}", prefix, key),
+                    None => String::from(key),
+                };
+                flatten_nested_settings(Some(&name), value, listeners, settings);
+            }
+        }
+        _ => {
+            if let Some(prefix) = prefix {
+                warn!("g:neovide contains the unknown setting {}", prefix);
+            }
+        }
+    }
+}
+
+// What users can find out about a setting without reading the source.
+#[derive(Clone)]
+struct SettingDescription {
+    default: Value,
+    type_name: String,
+}
+
+// The Settings struct acts as a global container where each of Neovide's subsystems can store
+// their own settings. It will also coordinate updates between Neovide and nvim to make sure the
+// settings remain consistent on both sides.
+// Note: Settings Neovide changes itself, for example when the window is made fullscreen, have to
+// go through set_and_sync. Plain set only changes the Neovide side, so nvim would keep a stale
+// value.
+// Settings are shared by every window, so only the nvim of one window drives them. The first
+// bridge to start owns them, and once its window closes another window takes over. Settings
+// notifications from any other nvim are ignored.
+pub struct Settings {
+    settings: RwLock<HashMap<TypeId, Box<dyn Any + Send + Sync>>>,
+    listeners: RwLock<HashMap<String, UpdateHandlerFunc>>,
+    readers: RwLock<HashMap<String, ReaderFunc>>,
+    descriptions: RwLock<HashMap<String, SettingDescription>>,
+    overrides: RwLock<HashMap<String, SettingOverride>>,
+    pending_syncs: RwLock<HashMap<String, Vec<Value>>>,
+    owner: RwLock<Weak<Bridge>>,
+    version: AtomicU64,
+}
+
+// A copy of one settings object which is only refreshed after a setting changed, so that code
+// running every frame doesn't go through the settings lock.
+pub struct SettingsCache<T> {
+    version: Option<u64>,
+    value: T,
+}
+
+impl<T: Clone + Send + Sync + 'static> SettingsCache<T> {
+    pub fn new() -> SettingsCache<T> {
+        SettingsCache {
+            version: None,
+            value: SETTINGS.get::<T>(),
+        }
+    }
+
+    // Returns true if the settings changed since the last refresh. The first refresh always does,
+    // so that values set before the cache existed get applied.
+    pub fn refresh(&mut self) -> bool {
+        let version = SETTINGS.version();
+        if self.version == Some(version) {
+            return false;
+        }
+
+        self.version = Some(version);
+        self.value = SETTINGS.get::<T>();
+        true
+    }
+
+    pub fn get(&mut self) -> &T {
+        self.refresh();
+        &self.value
+    }
+}
+
+impl Settings {
+    fn new() -> Settings {
+        Settings {
+            settings: RwLock::new(HashMap::new()),
+            listeners: RwLock::new(HashMap::new()),
+            readers: RwLock::new(HashMap::new()),
+            descriptions: RwLock::new(HashMap::new()),
+            overrides: RwLock::new(HashMap::new()),
+            pending_syncs: RwLock::new(HashMap::new()),
+            owner: RwLock::new(Weak::new()),
+            version: AtomicU64::new(0),
+        }
+    }
+
+    pub fn set_setting_handlers(
+        &self,
+        property_name: &str,
+        update_func: UpdateHandlerFunc,
+        reader_func: ReaderFunc,
+        type_name: &str,
+    ) {
+        self.listeners
+            .write()
+            .insert(String::from(property_name), update_func);
+        self.readers
+            .write()
+            .insert(String::from(property_name), reader_func);
+        // Registration happens before any value is read from nvim, so the current value is the
+        // default.
+        self.descriptions.write().insert(
+            String::from(property_name),
+            SettingDescription {
+                default: reader_func(),
+                type_name: short_type_name(type_name),
+            },
+        );
+    }
+
+    pub fn set<T: Clone + Send + Sync + 'static>(&self, t: &T) {
+        let type_id: TypeId = TypeId::of::<T>();
+        let t: T = (*t).clone();
+        self.settings.write().insert(type_id, Box::new(t));
+        self.version.fetch_add(1, Ordering::Release);
+    }
+
+    // Changes whenever any settings object is set.
+    pub fn version(&self) -> u64 {
+        self.version.load(Ordering::Acquire)
+    }
+
+    pub fn get<'a, T: Clone + Send + Sync + 'static>(&'a self) -> T {
+        let read_lock = self.settings.read();
+        let boxed = &read_lock
+            .get(&TypeId::of::<T>())
+            .expect("Trying to retrieve a settings object that doesn't exist");
+        let value: &T = boxed
+            .downcast_ref::<T>()
+            .expect("Attempted to extract a settings object of the wrong type");
+        (*value).clone()
+    }
+
+    // Makes the bridge the owner of the settings unless another one already is. Returns true if
+    // the bridge owns them afterwards.
+    pub fn claim(&self, bridge: &Arc<Bridge>) -> bool {
+        let mut owner = self.owner.write();
+        match owner.upgrade() {
+            Some(current_owner) => Arc::ptr_eq(&current_owner, bridge),
+            None => {
+                *owner = Arc::downgrade(bridge);
+                true
+            }
+        }
+    }
+
+    // Called when a window closes. Returns true if its bridge owned the settings, in which case
+    // another window should claim them.
+    pub fn release(&self, bridge: &Arc<Bridge>) -> bool {
+        let mut owner = self.owner.write();
+        let is_owner = Weak::ptr_eq(&*owner, &Arc::downgrade(bridge));
+        if is_owner {
+            *owner = Weak::new();
+        }
+        is_owner
+    }
+
+    pub fn is_owner(&self, bridge: &Arc<Bridge>) -> bool {
+        Weak::ptr_eq(&*self.owner.read(), &Arc::downgrade(bridge))
+    }
+
+    // Sets a settings object changed by Neovide itself and writes every value which changed to its
+    // g:neovide_ variable in the nvim owning the settings. A setting with an active override
+    // keeps the override, so like any other change to g:neovide_ the new value only replaces the
+    // global value which is restored once the override goes away.
+    pub fn set_and_sync<T: Clone + Send + Sync + 'static>(&self, t: &T) {
+        let previous_values = self.read_values();
+        self.set(t);
+
+        let changed_values = self
+            .read_values()
+            .into_iter()
+            .filter(|(name, value)| previous_values.get(name) != Some(value))
+            .collect::<Vec<(String, Value)>>();
+
+        {
+            let listeners = self.listeners.read();
+            let mut overrides = self.overrides.write();
+            for (name, value) in changed_values.iter() {
+                if let Some(setting_override) = overrides.get_mut(name) {
+                    setting_override.global = value.clone();
+                    listeners.get(name).unwrap()(setting_override.value.clone()).ok();
+                }
+            }
+        }
+
+        let owner = match self.owner.read().upgrade() {
+            Some(owner) => owner,
+            None => return,
+        };
+
+        let mut pending_syncs = self.pending_syncs.write();
+        for (name, value) in changed_values {
+            pending_syncs
+                .entry(name.clone())
+                .or_insert_with(Vec::new)
+                .push(value.clone());
+            owner.queue_command(UiCommand::SetVariable {
+                name: format!("neovide_{}", name),
+                value,
+            });
+        }
+    }
+
+    fn read_values(&self) -> HashMap<String, Value> {
+        self.readers
+            .read()
+            .iter()
+            .map(|(name, reader)| (name.clone(), reader()))
+            .collect()
+    }
+
+    // Writing a g:neovide_ variable triggers its dictwatcher, which sends the value straight back.
+    // Those echoes are dropped, since the value is already set and an older echo arriving after a
+    // newer sync would otherwise revert it. Any other value means the user changed the variable
+    // in the meantime, which wins.
+    fn is_sync_echo(&self, name: &str, value: &Value) -> bool {
+        let mut pending_syncs = self.pending_syncs.write();
+        let pending_values = match pending_syncs.get_mut(name) {
+            Some(pending_values) => pending_values,
+            None => return false,
+        };
+
+        let echo_index = pending_values
+            .iter()
+            .position(|pending_value| pending_value == value);
+        match echo_index {
+            Some(echo_index) => {
+                pending_values.drain(..=echo_index);
+                if pending_values.is_empty() {
+                    pending_syncs.remove(name);
+                }
+                true
+            }
+            None => {
+                pending_syncs.remove(name);
+                false
+            }
+        }
+    }
+
+    // Reads the current value of every registered setting. Returns None instead of blocking when
+    // the registry or the settings objects are locked. The readers go through get, whose recursive
+    // read can't block while the read lock taken here is held.
+    pub fn try_read_values(&self) -> Option<Vec<(String, Value)>> {
+        let _settings = self.settings.try_read_recursive()?;
+        let readers = self.readers.try_read()?;
+        let mut values = readers
+            .iter()
+            .map(|(name, reader)| (name.clone(), reader()))
+            .collect::<Vec<(String, Value)>>();
+        values.sort_by(|(a, _), (b, _)| a.cmp(b));
+        Some(values)
+    }
+
+    // Every registered setting with its current value, default and type, sorted by name. Answers
+    // the settings introspection request.
+    pub fn describe(&self) -> Value {
+        let readers = self.readers.read();
+        let descriptions = self.descriptions.read();
+        let mut names = readers.keys().cloned().collect::<Vec<String>>();
+        names.sort();
+
+        let settings = names
+            .into_iter()
+            .filter_map(|name| {
+                let reader = readers.get(&name)?;
+                let description = descriptions.get(&name)?;
+                Some(Value::Map(vec![
+                    (Value::from("name"), Value::from(name.as_str())),
+                    (Value::from("value"), reader()),
+                    (Value::from("default"), description.default.clone()),
+                    (
+                        Value::from("type"),
+                        Value::from(description.type_name.as_str()),
+                    ),
+                ]))
+            })
+            .collect();
+        Value::Array(settings)
+    }
+
+    pub async fn read_initial_values(&self, nvim: &Neovim<NeovimWriter>) {
+        let keys: Vec<String> = self.listeners.read().keys().cloned().collect();
+
+        for name in keys {
+            let variable_name = format!("neovide_{}", name.to_string());
+            match nvim.get_var(&variable_name).await {
+                Ok(value) => {
+                    if let Err(message) = self.apply_global(&name, value) {
+                        let error = SettingError::new(name, SettingSource::Global, message);
+                        self.reject_value(nvim, error).await;
+                    }
+                }
+                Err(error) => {
+                    warn!("Initial value load failed for {}: {}", name, error);
+                    let setting = self.readers.read().get(&name).unwrap()();
+                    nvim.set_var(&variable_name, setting).await.ok();
+                }
+            }
+        }
+
+        // Nested values are applied last so they win over the flat variables they duplicate
+        if let Ok(nested) = nvim.get_var("neovide").await {
+            for error in self.apply_nested(nested) {
+                self.reject_value(nvim, error).await;
+            }
+        }
+    }
+
+    // Notifications are sent to the given rpc channel which is the one the ui is attached on.
+    // Reattaching runs this again for the same nvim, so watchers from before are removed first.
+    pub async fn setup_changed_listeners(&self, nvim: &Neovim<NeovimWriter>, channel: u64) {
+        let mut keys: Vec<String> = self.listeners.read().keys().cloned().collect();
+        keys.sort();
+
+        for name in keys.iter() {
+            let vimscript = format!(
+                concat!(
+                    "exe \"",
+                    "fun! NeovideNotify{0}Changed(d, k, z)\n",
+                    "call rpcnotify({1}, 'setting_changed', '{0}', g:neovide_{0})\n",
+                    "endf\n",
+                    "silent! call dictwatcherdel(g:, 'neovide_{0}', 'NeovideNotify{0}Changed')\n",
+                    "call dictwatcheradd(g:, 'neovide_{0}', 'NeovideNotify{0}Changed')\"",
+                ),
+                name, channel
+            );
+            if let Err(error) = nvim.command(&vimscript).await {
+                report_recoverable_error(
+                    &format!("Could not setup setting notifier for {}", name),
+                    error,
+                );
+            }
+        }
+
+        // Only assigning g:neovide as a whole is noticed, not changes inside the dictionary.
+        let nested_vimscript = format!(
+            concat!(
+                "exe \"",
+                "fun! NeovideNotifyNestedChanged(d, k, z)\n",
+                "call rpcnotify({0}, 'nested_settings_changed', get(g:, 'neovide', {{}}))\n",
+                "endf\n",
+                "silent! call dictwatcherdel(g:, 'neovide', 'NeovideNotifyNestedChanged')\n",
+                "call dictwatcheradd(g:, 'neovide', 'NeovideNotifyNestedChanged')\"",
+            ),
+            channel
+        );
+        if let Err(error) = nvim.command(&nested_vimscript).await {
+            report_recoverable_error("Could not setup the g:neovide notifier", error);
+        }
+
+        // Buffer and window overrides are collected by the runtime plugin whenever the cursor
+        // moves to another buffer or window. It also watches the variables of every buffer and
+        // window it has seen, so that changes to the current ones apply right away.
+        let names = keys
+            .iter()
+            .map(|name| format!("'{}'", name))
+            .collect::<Vec<String>>()
+            .join(", ");
+        let override_commands = [
+            String::from("augroup NeovideSettingOverrides"),
+            String::from("autocmd!"),
+            format!(
+                "autocmd BufEnter,WinEnter,TermOpen * call neovide#notify_overrides({}, [{}])",
+                channel, names
+            ),
+            String::from("augroup END"),
+        ];
+        for command in override_commands.iter() {
+            if let Err(error) = nvim.command(command).await {
+                report_recoverable_error("Could not setup setting overrides", error);
+                break;
+            }
+        }
+    }
+
+    // Applies a value from g:neovide_<name> or g:neovide. While the setting is overridden the new
+    // value only replaces the global value which is restored once the override goes away.
+    fn apply_global(&self, name: &str, value: Value) -> Result<(), String> {
+        let listener = match self.listeners.read().get(name) {
+            Some(listener) => *listener,
+            None => {
+                warn!("Received a value for unknown setting {}", name);
+                return Ok(());
+            }
+        };
+
+        listener(value)?;
+
+        if let Some(setting_override) = self.overrides.write().get_mut(name) {
+            setting_override.global = self.readers.read().get(name).unwrap()();
+            listener(setting_override.value.clone()).ok();
+        }
+        Ok(())
+    }
+
+    fn apply_nested(&self, nested: Value) -> Vec<SettingError> {
+        let mut settings = Vec::new();
+        flatten_nested_settings(None, nested, &self.listeners.read(), &mut settings);
+
+        settings
+            .into_iter()
+            .filter_map(|(name, value)| {
+                self.apply_global(&name, value)
+                    .err()
+                    .map(|message| SettingError::new(name, SettingSource::Nested, message))
+            })
+            .collect()
+    }
+
+    // Replaces the current overrides with the given ones. Settings which are no longer
+    // overridden get their global value back.
+    fn apply_overrides(&self, new_overrides: Vec<(String, Value)>) -> Vec<SettingError> {
+        let listeners = self.listeners.read();
+        let readers = self.readers.read();
+        let mut overrides = self.overrides.write();
+
+        let stale_names = overrides
+            .keys()
+            .filter(|name| !new_overrides.iter().any(|(new_name, _)| new_name == *name))
+            .cloned()
+            .collect::<Vec<String>>();
+        for name in stale_names {
+            let setting_override = overrides.remove(&name).unwrap();
+            listeners.get(&name).unwrap()(setting_override.global).ok();
+        }
+
+        let mut errors = Vec::new();
+        for (name, value) in new_overrides {
+            let (listener, reader) = match (listeners.get(&name), readers.get(&name)) {
+                (Some(listener), Some(reader)) => (listener, reader),
+                _ => {
+                    warn!("Received an override for unknown setting {}", name);
+                    continue;
+                }
+            };
+
+            let global = match overrides.get(&name) {
+                Some(setting_override) if setting_override.value == value => continue,
+                Some(setting_override) => setting_override.global.clone(),
+                None => reader(),
+            };
+
+            match listener(value.clone()) {
+                Ok(()) => {
+                    overrides.insert(name, SettingOverride::new(global, value));
+                }
+                Err(message) => {
+                    errors.push(SettingError::new(name, SettingSource::Override, message));
+                }
+            }
+        }
+        errors
+    }
+
+    // Tells the user why a value was rejected. Flat g: variables also get the accepted value
+    // back so that nvim and Neovide agree again.
+    pub async fn reject_value(&self, nvim: &Neovim<NeovimWriter>, error: SettingError) {
+        if error.source == SettingSource::Global {
+            let current_value = match self.overrides.read().get(&error.name) {
+                Some(setting_override) => Some(setting_override.global.clone()),
+                None => self.readers.read().get(&error.name).map(|reader| reader()),
+            };
+            if let Some(current_value) = current_value {
+                let variable_name = format!("neovide_{}", error.name);
+                if let Err(set_error) = nvim.set_var(&variable_name, current_value).await {
+                    warn!("Could not reset g:{}: {}", variable_name, set_error);
+                }
+            }
+        }
+
+        let message = format!("{}: {}", error.variable(), error.message);
+        if let Err(write_error) = nvim.err_writeln(&message).await {
+            error!("{} ({})", message, write_error);
+        }
+    }
+
+    // Handles the setting_changed, nested_settings_changed and setting_overrides notifications.
+    // Errors are returned for values the setting rejected. Malformed notifications are only
+    // logged since the user can't do anything about them.
+    pub fn handle_changed_notification(
+        &self,
+        event_name: &str,
+        arguments: Vec<Value>,
+    ) -> Vec<SettingError> {
+        let mut arguments = arguments.into_iter();
+        match event_name {
+            "setting_changed" => {
+                let (name, value) = match (arguments.next(), arguments.next()) {
+                    (Some(name), Some(value)) => (name, value),
+                    _ => {
+                        warn!("Setting changed notification is missing its name or value");
+                        return Vec::new();
+                    }
+                };
+
+                let name: Result<String, _> = name.try_into();
+                match name {
+                    Ok(name) if self.is_sync_echo(&name, &value) => Vec::new(),
+                    Ok(name) => self
+                        .apply_global(&name, value)
+                        .err()
+                        .map(|message| SettingError::new(name, SettingSource::Global, message))
+                        .into_iter()
+                        .collect(),
+                    Err(name) => {
+                        warn!("Setting changed notification has an invalid name {}", name);
+                        Vec::new()
+                    }
+                }
+            }
+            "nested_settings_changed" => match arguments.next() {
+                Some(nested) => self.apply_nested(nested),
+                None => Vec::new(),
+            },
+            "setting_overrides" => match arguments.next() {
+                Some(Value::Map(entries)) => self.apply_overrides(
+                    entries
+                        .into_iter()
+                        .filter_map(|(name, value)| Some((String::from(name.as_str()?), value)))
+                        .collect(),
+                ),
+                _ => {
+                    warn!("Setting overrides notification is missing its overrides");
+                    Vec::new()
+                }
+            },
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn is_settings_notification(event_name: &str) -> bool {
+        match event_name {
+            "setting_changed" | "nested_settings_changed" | "setting_overrides" => true,
+            _ => false,
+        }
+    }
+}
@@ -1,29 +1,51 @@
-use std::sync::atomic::Ordering;
+use std::cell::RefCell;
+use std::fs;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 use std::thread::sleep;
 use std::time::{Duration, Instant};
 
 use log::{debug, error, info, trace};
 use skulpin::sdl2;
 use skulpin::sdl2::event::{Event, WindowEvent};
-use skulpin::sdl2::keyboard::Keycode;
+use skulpin::sdl2::keyboard::{Keycode, Mod};
 use skulpin::sdl2::video::FullscreenType;
-use skulpin::sdl2::Sdl;
+use skulpin::sdl2::{EventPump, Sdl};
 use skulpin::{
     CoordinateSystem, LogicalSize, PhysicalSize, PresentMode, Renderer as SkulpinRenderer,
     RendererBuilder, Sdl2Window, Window,
 };
 
-use crate::bridge::{produce_neovim_keybinding_string, UiCommand, BRIDGE};
-use crate::editor::EDITOR;
-use crate::redraw_scheduler::REDRAW_SCHEDULER;
-use crate::renderer::Renderer;
+use crate::bridge::{produce_neovim_keybinding_string, Bridge, GuiRequest, UiCommand};
+use crate::clock::CLOCK;
+use crate::command_line;
+use crate::crash_report::CRASH_REPORTER;
+use crate::editor::FontOptions;
+use crate::remote;
+use crate::renderer::{
+    FontCaches, Renderer, RendererSettings, SharedFontCaches, MAX_ZOOM, MIN_ZOOM,
+};
 use crate::settings::*;
+use crate::window_state::{self, maximized_requested, WindowState};
 use crate::INITIAL_DIMENSIONS;
 
 #[derive(RustEmbed)]
 #[folder = "assets/"]
 struct Asset;
 
+const ZOOM_STEP: f32 = 1.1;
+
+lazy_static! {
+    static ref NEW_WINDOW_REQUESTS: AtomicUsize = AtomicUsize::new(0);
+}
+
+// Asks the ui loop to open another window with its own nvim process. Windows can only be created
+// on the main thread, so the request is picked up on the next frame.
+pub fn request_new_window() {
+    NEW_WINDOW_REQUESTS.fetch_add(1, Ordering::Relaxed);
+}
+
 #[cfg(target_os = "windows")]
 fn windows_fix_dpi() {
     use winapi::shared::windef::DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2;
@@ -33,21 +55,46 @@ fn windows_fix_dpi() {
     }
 }
 
-fn handle_new_grid_size(new_size: LogicalSize, renderer: &Renderer) {
+// Builds the dictionary answers of the runtime api.
+fn value_map(entries: Vec<(&str, Value)>) -> Value {
+    Value::Map(
+        entries
+            .into_iter()
+            .map(|(name, value)| (Value::from(name), value))
+            .collect(),
+    )
+}
+
+// A refresh rate of zero is treated as one frame per second instead of dividing by zero.
+fn frame_length(refresh_rate: u64) -> Duration {
+    Duration::from_secs_f32(1.0 / refresh_rate.max(1) as f32)
+}
+
+fn handle_new_grid_size(new_size: LogicalSize, renderer: &Renderer, bridge: &Bridge) {
     if new_size.width > 0 && new_size.height > 0 {
         let new_width = ((new_size.width + 1) as f32 / renderer.font_width) as u32;
         let new_height = ((new_size.height + 1) as f32 / renderer.font_height) as u32;
         // Add 1 here to make sure resizing doesn't change the grid size on startup
-        BRIDGE.queue_command(UiCommand::Resize {
+        bridge.queue_command(UiCommand::Resize {
             width: new_width,
             height: new_height,
         });
     }
 }
 
+// Keyboard input is collected over a frame and sent once the frame's events are all handled.
+#[derive(Default)]
+struct FrameInput {
+    keycode: Option<Keycode>,
+    keytext: Option<String>,
+    ignore_text: bool,
+}
+
 struct WindowWrapper {
     context: Sdl,
     window: sdl2::video::Window,
+    bridge: Arc<Bridge>,
+    frame_input: FrameInput,
     skulpin_renderer: SkulpinRenderer,
     renderer: Renderer,
     mouse_down: bool,
@@ -58,45 +105,76 @@ struct WindowWrapper {
     fullscreen: bool,
     cached_size: (u32, u32),
     cached_position: (i32, i32),
+    normal_size: (u32, u32),
+    normal_position: (i32, i32),
+    settings: SettingsCache<WindowSettings>,
 }
 
-pub fn window_geometry() -> Result<(u64, u64), String> {
-    let prefix = "--geometry=";
-
-    std::env::args()
-        .filter(|arg| arg.starts_with(prefix))
-        .next()
-        .map_or(Ok(INITIAL_DIMENSIONS), |arg| {
-            let input = &arg[prefix.len()..];
-            let invalid_parse_err = format!(
-                "Invalid geometry: {}\nValid format: <width>x<height>",
-                input
-            );
-
-            input
-                .split('x')
-                .map(|dimension| {
-                    dimension
-                        .parse::<u64>()
-                        .or(Err(invalid_parse_err.as_str()))
-                        .and_then(|dimension| {
-                            if dimension > 0 {
-                                Ok(dimension)
-                            } else {
-                                Err("Invalid geometry: Window dimensions should be greater than 0.")
-                            }
-                        })
-                })
-                .collect::<Result<Vec<_>, &str>>()
-                .and_then(|dimensions| {
-                    if let [width, height] = dimensions[..] {
-                        Ok((width, height))
+// Parses <width>x<height> with an optional +<x>+<y> window position. The size is in grid cells
+// and the position in screen coordinates.
+fn parse_geometry(input: &str) -> Result<((u64, u64), Option<(i32, i32)>), String> {
+    let invalid_parse_err = format!(
+        "Invalid geometry: {}\nValid format: <width>x<height>[+<x>+<y>]",
+        input
+    );
+
+    let mut parts = input.splitn(2, '+');
+    let size_input = parts.next().unwrap_or_default();
+    let position = match parts.next() {
+        Some(position_input) => {
+            let coordinates = position_input
+                .split('+')
+                .map(|coordinate| coordinate.parse::<i32>())
+                .collect::<Result<Vec<i32>, _>>()
+                .map_err(|_| invalid_parse_err.clone())?;
+            if let [x, y] = coordinates[..] {
+                Some((x, y))
+            } else {
+                return Err(invalid_parse_err);
+            }
+        }
+        None => None,
+    };
+
+    size_input
+        .split('x')
+        .map(|dimension| {
+            dimension
+                .parse::<u64>()
+                .or(Err(invalid_parse_err.as_str()))
+                .and_then(|dimension| {
+                    if dimension > 0 {
+                        Ok(dimension)
                     } else {
-                        Err(invalid_parse_err.as_str())
+                        Err("Invalid geometry: Window dimensions should be greater than 0.")
                     }
                 })
-                .map_err(|msg| msg.to_owned())
         })
+        .collect::<Result<Vec<_>, &str>>()
+        .and_then(|dimensions| {
+            if let [width, height] = dimensions[..] {
+                Ok(((width, height), position))
+            } else {
+                Err(invalid_parse_err.as_str())
+            }
+        })
+        .map_err(|msg| msg.to_owned())
+}
+
+fn geometry_argument() -> Option<String> {
+    command_line::option_value("--geometry").flatten()
+}
+
+pub fn window_geometry() -> Result<(u64, u64), String> {
+    geometry_argument().map_or(Ok(INITIAL_DIMENSIONS), |input| {
+        parse_geometry(&input).map(|(size, _)| size)
+    })
+}
+
+pub fn window_position() -> Option<(i32, i32)> {
+    geometry_argument()
+        .and_then(|input| parse_geometry(&input).ok())
+        .and_then(|(_, position)| position)
 }
 
 pub fn window_geometry_or_default() -> (u64, u64) {
@@ -104,8 +182,13 @@ pub fn window_geometry_or_default() -> (u64, u64) {
 }
 
 impl WindowWrapper {
-    pub fn new() -> WindowWrapper {
-        let context = sdl2::init().expect("Failed to initialize sdl2");
+    // The saved state, if any, decides the size and placement instead of --geometry.
+    pub fn new(
+        context: &Sdl,
+        bridge: Arc<Bridge>,
+        font_caches: SharedFontCaches,
+        saved_state: Option<WindowState>,
+    ) -> WindowWrapper {
         let video_subsystem = context
             .video()
             .expect("Failed to create sdl video subsystem");
@@ -113,12 +196,34 @@ impl WindowWrapper {
 
         let (width, height) = window_geometry_or_default();
 
-        let renderer = Renderer::new();
-        let logical_size = LogicalSize {
-            width: (width as f32 * renderer.font_width) as u32,
-            height: (height as f32 * renderer.font_height + 1.0) as u32,
+        let renderer = Renderer::new(
+            bridge.editor.clone(),
+            bridge.redraw_scheduler.clone(),
+            font_caches,
+        );
+        let logical_size = match &saved_state {
+            Some(state) => LogicalSize {
+                width: state.size.0,
+                height: state.size.1,
+            },
+            None => LogicalSize {
+                width: (width as f32 * renderer.font_width) as u32,
+                height: (height as f32 * renderer.font_height + 1.0) as u32,
+            },
         };
 
+        // Saved positions are only used while the monitor they were on is still there, since
+        // the window could end up off screen otherwise.
+        let position = window_position().or_else(|| {
+            let state = saved_state.as_ref()?;
+            let bounds = video_subsystem.display_bounds(state.monitor).ok()?;
+            if bounds.contains_point(state.position) {
+                Some(state.position)
+            } else {
+                None
+            }
+        });
+
         #[cfg(target_os = "windows")]
         windows_fix_dpi();
         sdl2::hint::set("SDL_MOUSE_FOCUS_CLICKTHROUGH", "1");
@@ -135,9 +240,13 @@ impl WindowWrapper {
         // };
         // info!("icon created");
 
-        let sdl_window = video_subsystem
-            .window("Neovide", logical_size.width, logical_size.height)
-            .position_centered()
+        let mut window_builder =
+            video_subsystem.window("Neovide", logical_size.width, logical_size.height);
+        match position {
+            Some((x, y)) => window_builder.position(x, y),
+            None => window_builder.position_centered(),
+        };
+        let mut sdl_window = window_builder
             .allow_highdpi()
             .resizable()
             .vulkan()
@@ -145,6 +254,23 @@ impl WindowWrapper {
             .expect("Failed to create window");
         info!("window created");
 
+        let normal_position = sdl_window.position();
+        let normal_size = sdl_window.size();
+        let maximized = saved_state
+            .as_ref()
+            .map(|state| state.maximized)
+            .unwrap_or(false);
+        if maximized || maximized_requested() {
+            sdl_window.maximize();
+        }
+
+        // Fullscreen is a setting, so restoring it goes through nvim like any other change
+        if saved_state.map(|state| state.fullscreen).unwrap_or(false) {
+            let mut settings = SETTINGS.get::<WindowSettings>();
+            settings.fullscreen = true;
+            SETTINGS.set_and_sync(&settings);
+        }
+
         let skulpin_renderer = {
             let sdl_window_wrapper = Sdl2Window::new(&sdl_window);
             RendererBuilder::new()
@@ -159,8 +285,10 @@ impl WindowWrapper {
         info!("renderer created");
 
         WindowWrapper {
-            context,
+            context: context.clone(),
             window: sdl_window,
+            bridge,
+            frame_input: FrameInput::default(),
             skulpin_renderer,
             renderer,
             mouse_down: false,
@@ -174,6 +302,9 @@ impl WindowWrapper {
             fullscreen: false,
             cached_size: (0, 0),
             cached_position: (0, 0),
+            normal_size,
+            normal_position,
+            settings: SettingsCache::new(),
         }
     }
 
@@ -227,10 +358,40 @@ impl WindowWrapper {
         }
 
         self.fullscreen = !self.fullscreen;
+        CRASH_REPORTER.set_gui_state("fullscreen", self.fullscreen.to_string());
+    }
+
+    fn is_maximized(&self) -> bool {
+        self.window.window_flags() & sdl2::sys::SDL_WindowFlags::SDL_WINDOW_MAXIMIZED as u32 != 0
+    }
+
+    // Tracks the geometry to restore a maximized or fullscreen window to, since sdl only reports
+    // the current one.
+    fn remember_normal_geometry(&mut self) {
+        if !self.fullscreen && !self.is_maximized() {
+            self.normal_size = self.window.size();
+            self.normal_position = self.window.position();
+        }
+    }
+
+    pub fn window_state(&self) -> WindowState {
+        let (position, size) = if self.fullscreen {
+            (self.cached_position, self.cached_size)
+        } else {
+            (self.normal_position, self.normal_size)
+        };
+
+        WindowState {
+            position,
+            size,
+            maximized: self.is_maximized(),
+            fullscreen: self.fullscreen,
+            monitor: self.window.display_index().unwrap_or(0),
+        }
     }
 
     pub fn synchronize_settings(&mut self) {
-        let editor_title = { EDITOR.lock().title.clone() };
+        let editor_title = { self.bridge.editor.lock().title.clone() };
 
         if self.title != editor_title {
             self.title = editor_title;
@@ -239,16 +400,16 @@ impl WindowWrapper {
                 .expect("Could not set title");
         }
 
-        let transparency = { SETTINGS.get::<WindowSettings>().transparency };
+        // The settings cache only goes back to the settings lock after a setting changed, so
+        // comparing against the applied values every frame is cheap.
+        let transparency = self.settings.get().transparency;
 
-        if let Ok(opacity) = self.window.opacity() {
-            if opacity != transparency {
-                self.window.set_opacity(transparency).ok();
-                self.transparency = transparency;
-            }
+        if (self.transparency - transparency).abs() > std::f32::EPSILON {
+            self.window.set_opacity(transparency).ok();
+            self.transparency = transparency;
         }
 
-        let fullscreen = { SETTINGS.get::<WindowSettings>().fullscreen };
+        let fullscreen = self.settings.get().fullscreen;
 
         if self.fullscreen != fullscreen {
             self.toggle_fullscreen();
@@ -256,7 +417,7 @@ impl WindowWrapper {
     }
 
     pub fn handle_quit(&mut self) {
-        BRIDGE.queue_command(UiCommand::Quit);
+        self.bridge.queue_command(UiCommand::Quit);
     }
 
     pub fn handle_keyboard_input(&mut self, keycode: Option<Keycode>, text: Option<String>) {
@@ -271,12 +432,50 @@ impl WindowWrapper {
             );
         }
 
+        // Ctrl+Shift+F5 restarts nvim instead of being sent to it
+        if keycode == Some(Keycode::F5)
+            && modifiers.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD)
+            && modifiers.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD)
+        {
+            self.bridge.queue_command(UiCommand::Restart);
+            return;
+        }
+
+        // Ctrl+= and Ctrl+- zoom the font in and out, Ctrl+0 resets it
+        if self.settings.get().zoom_bindings && modifiers.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD)
+        {
+            let zoom = SETTINGS.get::<RendererSettings>().zoom;
+            let new_zoom = match keycode {
+                Some(Keycode::Equals) | Some(Keycode::Plus) | Some(Keycode::KpPlus) => {
+                    Some(zoom * ZOOM_STEP)
+                }
+                Some(Keycode::Minus) | Some(Keycode::KpMinus) => Some(zoom / ZOOM_STEP),
+                Some(Keycode::Num0) | Some(Keycode::Kp0) => Some(1.0),
+                _ => None,
+            };
+
+            if let Some(new_zoom) = new_zoom {
+                self.set_zoom(new_zoom);
+                return;
+            }
+        }
+
         if let Some(keybinding_string) = produce_neovim_keybinding_string(keycode, text, modifiers)
         {
-            BRIDGE.queue_command(UiCommand::Keyboard(keybinding_string));
+            self.bridge
+                .queue_command(UiCommand::Keyboard(keybinding_string));
         }
     }
 
+    // Zooming goes through the zoom setting so that nvim sees the new level. The renderer picks
+    // it up on the next frame and resizes the grid.
+    fn set_zoom(&mut self, zoom: f32) {
+        let mut settings = SETTINGS.get::<RendererSettings>();
+        settings.zoom = zoom.max(MIN_ZOOM).min(MAX_ZOOM);
+        SETTINGS.set_and_sync(&settings);
+        self.bridge.redraw_scheduler.queue_next_frame();
+    }
+
     pub fn handle_pointer_motion(&mut self, x: i32, y: i32) {
         let previous_position = self.mouse_position;
         let physical_size = PhysicalSize::new(
@@ -287,7 +486,7 @@ impl WindowWrapper {
         let sdl_window_wrapper = Sdl2Window::new(&self.window);
         self.mouse_position = physical_size.to_logical(sdl_window_wrapper.scale_factor());
         if self.mouse_down && previous_position != self.mouse_position {
-            BRIDGE.queue_command(UiCommand::Drag(
+            self.bridge.queue_command(UiCommand::Drag(
                 self.mouse_position.width,
                 self.mouse_position.height,
             ));
@@ -295,7 +494,7 @@ impl WindowWrapper {
     }
 
     pub fn handle_pointer_down(&mut self) {
-        BRIDGE.queue_command(UiCommand::MouseButton {
+        self.bridge.queue_command(UiCommand::MouseButton {
             action: String::from("press"),
             position: (self.mouse_position.width, self.mouse_position.height),
         });
@@ -303,7 +502,7 @@ impl WindowWrapper {
     }
 
     pub fn handle_pointer_up(&mut self) {
-        BRIDGE.queue_command(UiCommand::MouseButton {
+        self.bridge.queue_command(UiCommand::MouseButton {
             action: String::from("release"),
             position: (self.mouse_position.width, self.mouse_position.height),
         });
@@ -311,6 +510,16 @@ impl WindowWrapper {
     }
 
     pub fn handle_mouse_wheel(&mut self, x: i32, y: i32) {
+        let modifiers = self.context.keyboard().mod_state();
+        if y != 0
+            && self.settings.get().zoom_bindings
+            && modifiers.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD)
+        {
+            let zoom = SETTINGS.get::<RendererSettings>().zoom;
+            self.set_zoom(zoom * ZOOM_STEP.powi(y));
+            return;
+        }
+
         let vertical_input_type = if y > 0 {
             Some("up")
         } else if y < 0 {
@@ -320,7 +529,7 @@ impl WindowWrapper {
         };
 
         if let Some(input_type) = vertical_input_type {
-            BRIDGE.queue_command(UiCommand::Scroll {
+            self.bridge.queue_command(UiCommand::Scroll {
                 direction: input_type.to_string(),
                 position: (self.mouse_position.width, self.mouse_position.height),
             });
@@ -335,7 +544,7 @@ impl WindowWrapper {
         };
 
         if let Some(input_type) = horizontal_input_type {
-            BRIDGE.queue_command(UiCommand::Scroll {
+            self.bridge.queue_command(UiCommand::Scroll {
                 direction: input_type.to_string(),
                 position: (self.mouse_position.width, self.mouse_position.height),
             });
@@ -343,40 +552,244 @@ impl WindowWrapper {
     }
 
     pub fn handle_focus_lost(&mut self) {
-        BRIDGE.queue_command(UiCommand::FocusLost);
+        self.bridge.queue_command(UiCommand::FocusLost);
     }
 
     pub fn handle_focus_gained(&mut self) {
-        BRIDGE.queue_command(UiCommand::FocusGained);
-        REDRAW_SCHEDULER.queue_next_frame();
+        self.bridge.queue_command(UiCommand::FocusGained);
+        self.bridge.redraw_scheduler.queue_next_frame();
+        remote::set_target(&self.bridge);
+    }
+
+    pub fn handle_event(&mut self, event: Event) {
+        match event {
+            Event::DropFile { filename, .. } => {
+                self.bridge.queue_command(UiCommand::FileDrop(filename));
+            }
+            Event::KeyDown {
+                keycode: received_keycode,
+                ..
+            } => {
+                self.frame_input.keycode = received_keycode;
+            }
+            Event::TextInput { text, .. } => self.frame_input.keytext = Some(text),
+            Event::MouseMotion { x, y, .. } => self.handle_pointer_motion(x, y),
+            Event::MouseButtonDown { .. } => self.handle_pointer_down(),
+            Event::MouseButtonUp { .. } => self.handle_pointer_up(),
+            Event::MouseWheel { x, y, .. } => self.handle_mouse_wheel(x, y),
+            Event::Window {
+                win_event: WindowEvent::Close,
+                ..
+            } => self.handle_quit(),
+            Event::Window {
+                win_event: WindowEvent::FocusLost,
+                ..
+            } => self.handle_focus_lost(),
+            Event::Window {
+                win_event: WindowEvent::FocusGained,
+                ..
+            } => {
+                self.frame_input.ignore_text = true; // Ignore any text events on the first frame when focus is regained. https://github.com/Kethku/neovide/issues/193
+                self.handle_focus_gained();
+            }
+            Event::Window {
+                win_event: WindowEvent::Moved(..),
+                ..
+            }
+            | Event::Window {
+                win_event: WindowEvent::SizeChanged(..),
+                ..
+            } => {
+                self.remember_normal_geometry();
+                self.bridge.redraw_scheduler.queue_next_frame();
+            }
+            Event::Window { .. } => self.bridge.redraw_scheduler.queue_next_frame(),
+            _ => {}
+        }
     }
 
+    // Answers the runtime api requests which arrived since the last frame.
+    pub fn handle_gui_requests(&mut self) {
+        for (request, responder) in self.bridge.take_gui_requests() {
+            trace!("Gui request: {:?}", request);
+            responder.send(self.handle_gui_request(request)).ok();
+        }
+    }
+
+    fn handle_gui_request(&mut self, request: GuiRequest) -> Result<Value, String> {
+        match request {
+            GuiRequest::SetFont { name, size } => {
+                let mut editor = self.bridge.editor.lock();
+                let mut font_options =
+                    FontOptions::parse_with_current(&name, editor.font_options.as_ref());
+                if size.is_some() {
+                    font_options.size = size;
+                }
+                editor.font_options = Some(font_options);
+                self.bridge.redraw_scheduler.queue_next_frame();
+                Ok(Value::Nil)
+            }
+            GuiRequest::Screenshot(path) => {
+                let data = self
+                    .renderer
+                    .screenshot()
+                    .ok_or_else(|| String::from("Nothing has been drawn yet"))?;
+                fs::write(&path, data)
+                    .map_err(|error| format!("Could not write {:?}: {}", path, error))?;
+                Ok(Value::from(path.to_string_lossy().into_owned()))
+            }
+            GuiRequest::Zoom(zoom) => {
+                in_range(&zoom, MIN_ZOOM..=MAX_ZOOM)
+                    .map_err(|error| format!("Invalid zoom: {}", error))?;
+                self.set_zoom(zoom);
+                Ok(Value::Nil)
+            }
+            GuiRequest::ToggleFullscreen => {
+                let mut settings = SETTINGS.get::<WindowSettings>();
+                settings.fullscreen = !settings.fullscreen;
+                SETTINGS.set_and_sync(&settings);
+                Ok(Value::from(settings.fullscreen))
+            }
+            GuiRequest::GetMetrics => {
+                let (grid_width, grid_height) = {
+                    let editor = self.bridge.editor.lock();
+                    (editor.grid.width, editor.grid.height)
+                };
+                Ok(value_map(vec![
+                    ("font_width", Value::from(self.renderer.font_width as f64)),
+                    ("font_height", Value::from(self.renderer.font_height as f64)),
+                    ("grid_width", Value::from(grid_width)),
+                    ("grid_height", Value::from(grid_height)),
+                    ("zoom", Value::from(self.renderer.zoom() as f64)),
+                ]))
+            }
+            GuiRequest::GetCellSize => Ok(value_map(vec![
+                ("width", Value::from(self.renderer.font_width as f64)),
+                ("height", Value::from(self.renderer.font_height as f64)),
+            ])),
+            GuiRequest::GetWindowSize => {
+                let sdl_window_wrapper = Sdl2Window::new(&self.window);
+                let logical_size = sdl_window_wrapper.logical_size();
+                let physical_size = sdl_window_wrapper.physical_size();
+                Ok(value_map(vec![
+                    (
+                        "logical",
+                        value_map(vec![
+                            ("width", Value::from(logical_size.width)),
+                            ("height", Value::from(logical_size.height)),
+                        ]),
+                    ),
+                    (
+                        "physical",
+                        value_map(vec![
+                            ("width", Value::from(physical_size.width)),
+                            ("height", Value::from(physical_size.height)),
+                        ]),
+                    ),
+                ]))
+            }
+            GuiRequest::GetScaleFactor => {
+                let sdl_window_wrapper = Sdl2Window::new(&self.window);
+                Ok(Value::from(sdl_window_wrapper.scale_factor()))
+            }
+            GuiRequest::GetFont => Ok(value_map(vec![
+                (
+                    "name",
+                    self.renderer
+                        .font_name()
+                        .map(Value::from)
+                        .unwrap_or(Value::Nil),
+                ),
+                ("size", Value::from(self.renderer.font_size() as f64)),
+            ])),
+            GuiRequest::GetRefreshRate => {
+                Ok(Value::from(SETTINGS.get::<WindowSettings>().refresh_rate))
+            }
+            // The clock is shared, so pausing and stepping affects the animations of every window.
+            GuiRequest::PauseAnimations => {
+                CLOCK.pause();
+                Ok(Value::Nil)
+            }
+            GuiRequest::ResumeAnimations => {
+                CLOCK.resume();
+                self.bridge.redraw_scheduler.queue_next_frame();
+                Ok(Value::Nil)
+            }
+            GuiRequest::StepAnimations(seconds) => {
+                if !CLOCK.is_paused() {
+                    return Err(String::from("Animations have to be paused before stepping"));
+                }
+                if seconds < 0.0 {
+                    return Err(format!("Cannot step back in time by {}", seconds));
+                }
+                // Duration::from_secs_f32 panics on values it can't represent.
+                if !seconds.is_finite() || seconds >= u64::MAX as f32 {
+                    return Err(format!("Cannot step forward in time by {}", seconds));
+                }
+                CLOCK.step(Duration::from_secs_f32(seconds));
+                self.bridge.redraw_scheduler.queue_next_frame();
+                Ok(Value::Nil)
+            }
+            GuiRequest::SetParticleSeed(seed) => {
+                self.renderer.set_particle_seed(seed);
+                Ok(Value::Nil)
+            }
+        }
+    }
+
+    pub fn handle_frame_input(&mut self) {
+        let frame_input = std::mem::take(&mut self.frame_input);
+
+        if !frame_input.ignore_text {
+            self.handle_keyboard_input(frame_input.keycode, frame_input.keytext);
+        }
+    }
+
+    // Returns false once the window should close.
     pub fn draw_frame(&mut self) -> bool {
-        if !BRIDGE.running.load(Ordering::Relaxed) {
+        if !self.bridge.running.load(Ordering::Relaxed) {
+            return false;
+        }
+
+        // Nvim carries on without the window after detaching
+        if self.bridge.is_detached() {
             return false;
         }
 
         let sdl_window_wrapper = Sdl2Window::new(&self.window);
         let new_size = sdl_window_wrapper.logical_size();
         if self.previous_size != new_size {
-            handle_new_grid_size(new_size, &self.renderer);
+            handle_new_grid_size(new_size, &self.renderer, &self.bridge);
             self.previous_size = new_size;
+            CRASH_REPORTER.set_gui_state(
+                "window",
+                format!(
+                    "logical size {}x{} scale factor {}",
+                    new_size.width,
+                    new_size.height,
+                    sdl_window_wrapper.scale_factor()
+                ),
+            );
         }
 
         debug!("Render Triggered");
 
         let current_size = self.previous_size;
 
-        if REDRAW_SCHEDULER.should_draw() || SETTINGS.get::<WindowSettings>().no_idle {
+        if self.bridge.redraw_scheduler.should_draw() || self.settings.get().no_idle {
+            let refresh_rate = self.settings.get().refresh_rate;
             let renderer = &mut self.renderer;
+            let bridge = &self.bridge;
+            let dt = CLOCK.frame_delta(frame_length(refresh_rate));
 
             if self
                 .skulpin_renderer
                 .draw(&sdl_window_wrapper, |canvas, coordinate_system_helper| {
-                    let dt = 1.0 / (SETTINGS.get::<WindowSettings>().refresh_rate as f32);
-
                     if renderer.draw(canvas, &coordinate_system_helper, dt) {
-                        handle_new_grid_size(current_size, &renderer)
+                        handle_new_grid_size(current_size, &renderer, bridge)
+                    }
+                    if let Some(error) = renderer.take_font_error() {
+                        bridge.queue_command(UiCommand::ShowError(error));
                     }
                 })
                 .is_err()
@@ -388,6 +801,36 @@ impl WindowWrapper {
 
         return true;
     }
+
+    // Keeps the window open showing the message until the user closes it or presses a key.
+    pub fn show_error_overlay(&mut self, event_pump: &mut EventPump, message: &str) {
+        info!("Showing error overlay");
+        let frame_length = Duration::from_secs_f32(1.0 / 60.0);
+
+        loop {
+            for event in event_pump.poll_iter() {
+                match event {
+                    Event::Quit { .. } | Event::KeyDown { .. } => return,
+                    _ => {}
+                }
+            }
+
+            let sdl_window_wrapper = Sdl2Window::new(&self.window);
+            let renderer = &mut self.renderer;
+            if self
+                .skulpin_renderer
+                .draw(&sdl_window_wrapper, |canvas, coordinate_system_helper| {
+                    renderer.draw_error_overlay(canvas, &coordinate_system_helper, message);
+                })
+                .is_err()
+            {
+                error!("Render failed. Closing");
+                return;
+            }
+
+            sleep(frame_length);
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -396,93 +839,237 @@ struct WindowSettings {
     transparency: f32,
     no_idle: bool,
     fullscreen: bool,
+    zoom_bindings: bool,
 }
 
 pub fn initialize_settings() {
-    let no_idle = SETTINGS
-        .neovim_arguments
-        .contains(&String::from("--noIdle"));
+    let no_idle = command_line::has_argument("--noIdle");
 
     SETTINGS.set(&WindowSettings {
         refresh_rate: 60,
         transparency: 1.0,
         no_idle,
         fullscreen: false,
+        zoom_bindings: true,
     });
 
-    register_nvim_setting!("refresh_rate", WindowSettings::refresh_rate);
-    register_nvim_setting!("transparency", WindowSettings::transparency);
+    register_nvim_setting!("refresh_rate", WindowSettings::refresh_rate, |rate| {
+        greater_than(rate, 0)
+    });
+    register_nvim_setting!(
+        "transparency",
+        WindowSettings::transparency,
+        |transparency| { in_range(transparency, 0.0..=1.0) }
+    );
     register_nvim_setting!("no_idle", WindowSettings::no_idle);
     register_nvim_setting!("fullscreen", WindowSettings::fullscreen);
+    register_nvim_setting!("zoom_bindings", WindowSettings::zoom_bindings);
+}
+
+fn open_window(
+    context: &Sdl,
+    bridge: Arc<Bridge>,
+    font_caches: &SharedFontCaches,
+    saved_state: Option<WindowState>,
+) -> WindowWrapper {
+    let window = WindowWrapper::new(context, bridge, font_caches.clone(), saved_state);
+    remote::set_target(&window.bridge);
+    window
 }
 
+// Logs and returns why nvim exited, if that was unexpected. A detached nvim is still running, so
+// there is nothing to report for it.
+fn closed_window_error(window: &WindowWrapper) -> Option<String> {
+    if window.bridge.is_detached() {
+        return None;
+    }
+
+    let message = window.bridge.exit_status().error?;
+    error!("Window closed: {}", message);
+    Some(message)
+}
+
+// Runs every window on the main thread. Each window owns its bridge, so closing one only ends its
+// own nvim session. The process exits with the status of the last window to close.
 pub fn ui_loop() {
-    let mut window = WindowWrapper::new();
+    let context = sdl2::init().expect("Failed to initialize sdl2");
+    let font_caches = Rc::new(RefCell::new(FontCaches::new()));
+    // Only the first window is placed where the last one closed, later ones would cover it
+    let mut windows = vec![open_window(
+        &context,
+        Bridge::start_initial(),
+        &font_caches,
+        window_state::restorable_state(),
+    )];
+    let mut window_settings = SettingsCache::<WindowSettings>::new();
 
     info!("Starting window event loop");
-    let mut event_pump = window
-        .context
+    let mut event_pump = context
         .event_pump()
         .expect("Could not create sdl event pump");
 
-    loop {
+    let mut last_window = loop {
         let frame_start = Instant::now();
 
-        window.synchronize_settings();
+        for _ in 0..NEW_WINDOW_REQUESTS.swap(0, Ordering::Relaxed) {
+            info!("Opening new window");
+            windows.push(open_window(
+                &context,
+                Bridge::start_new_window(),
+                &font_caches,
+                None,
+            ));
+        }
 
-        let mut keycode = None;
-        let mut keytext = None;
-        let mut ignore_text_this_frame = false;
+        for window in windows.iter_mut() {
+            window.synchronize_settings();
+        }
 
         for event in event_pump.poll_iter() {
-            match event {
-                Event::Quit { .. } => window.handle_quit(),
-                Event::DropFile { filename, .. } => {
-                    BRIDGE.queue_command(UiCommand::FileDrop(filename));
-                }
-                Event::KeyDown {
-                    keycode: received_keycode,
-                    ..
-                } => {
-                    keycode = received_keycode;
-                }
-                Event::TextInput { text, .. } => keytext = Some(text),
-                Event::MouseMotion { x, y, .. } => window.handle_pointer_motion(x, y),
-                Event::MouseButtonDown { .. } => window.handle_pointer_down(),
-                Event::MouseButtonUp { .. } => window.handle_pointer_up(),
-                Event::MouseWheel { x, y, .. } => window.handle_mouse_wheel(x, y),
-                Event::Window {
-                    win_event: WindowEvent::FocusLost,
-                    ..
-                } => window.handle_focus_lost(),
-                Event::Window {
-                    win_event: WindowEvent::FocusGained,
-                    ..
-                } => {
-                    ignore_text_this_frame = true; // Ignore any text events on the first frame when focus is regained. https://github.com/Kethku/neovide/issues/193
-                    window.handle_focus_gained();
+            if let Event::Quit { .. } = event {
+                for window in windows.iter_mut() {
+                    window.handle_quit();
                 }
-                Event::Window { .. } => REDRAW_SCHEDULER.queue_next_frame(),
-                _ => {}
+                continue;
+            }
+
+            let window_id = event.get_window_id();
+            if let Some(window) = windows
+                .iter_mut()
+                .find(|window| Some(window.window.id()) == window_id)
+            {
+                window.handle_event(event);
+            }
+        }
+
+        let raise_requested = remote::take_raise_request();
+        let mut open_windows = Vec::with_capacity(windows.len());
+        let mut closed_windows = Vec::new();
+
+        for mut window in windows.drain(..) {
+            if raise_requested && remote::is_target(&window.bridge) {
+                window.window.raise();
+            }
+
+            window.handle_frame_input();
+            window.handle_gui_requests();
+
+            if window.draw_frame() {
+                open_windows.push(window);
+            } else {
+                closed_windows.push(window);
             }
         }
 
-        if !ignore_text_this_frame {
-            window.handle_keyboard_input(keycode, keytext);
+        // The last window shows its error before the process exits, so a window with an error is
+        // preferred for that. Any others closing at the same time are saved and logged.
+        if open_windows.is_empty() {
+            let last_index = closed_windows
+                .iter()
+                .rposition(|window| window.bridge.exit_status().error.is_some())
+                .or_else(|| closed_windows.len().checked_sub(1));
+            let last_window = last_index.map(|index| closed_windows.remove(index));
+
+            for window in closed_windows {
+                window.window_state().save();
+                closed_window_error(&window);
+            }
+            break last_window;
         }
 
-        if !window.draw_frame() {
-            break;
+        for window in closed_windows {
+            window.window_state().save();
+            if SETTINGS.release(&window.bridge) {
+                open_windows[0]
+                    .bridge
+                    .queue_command(UiCommand::WatchSettings);
+            }
+
+            // The other windows stay open, so the error is shown in one of them
+            if let Some(message) = closed_window_error(&window) {
+                open_windows[0]
+                    .bridge
+                    .queue_command(UiCommand::ShowError(format!(
+                        "A Neovide window closed: {}",
+                        message
+                    )));
+            }
         }
+        windows = open_windows;
 
         let elapsed = frame_start.elapsed();
-        let refresh_rate = { SETTINGS.get::<WindowSettings>().refresh_rate as f32 };
-        let frame_length = Duration::from_secs_f32(1.0 / refresh_rate);
+        let frame_length = frame_length(window_settings.get().refresh_rate);
 
         if elapsed < frame_length {
             sleep(frame_length - elapsed);
         }
+    };
+
+    let exit_code = match last_window.as_mut() {
+        Some(window) => {
+            window.window_state().save();
+            if let Some(message) = closed_window_error(window) {
+                window.show_error_overlay(&mut event_pump, &message);
+            }
+            window.bridge.exit_status().code
+        }
+        None => 0,
+    };
+
+    std::process::exit(exit_code);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_geometry_reads_size() {
+        assert_eq!(parse_geometry("80x24"), Ok(((80, 24), None)));
     }
 
-    std::process::exit(0);
+    #[test]
+    fn parse_geometry_reads_position() {
+        assert_eq!(
+            parse_geometry("80x24+10+20"),
+            Ok(((80, 24), Some((10, 20))))
+        );
+    }
+
+    #[test]
+    fn parse_geometry_reads_negative_offsets() {
+        assert_eq!(
+            parse_geometry("80x24+-1920+-20"),
+            Ok(((80, 24), Some((-1920, -20))))
+        );
+    }
+
+    #[test]
+    fn parse_geometry_rejects_malformed_input() {
+        for input in &[
+            "",
+            "80",
+            "80x",
+            "x24",
+            "80x24x3",
+            "ax24",
+            "80x-24",
+            "80x24+10",
+            "80x24+10+20+30",
+            "80x24+a+b",
+            "80x24+",
+        ] {
+            assert!(parse_geometry(input).is_err(), "{} was accepted", input);
+        }
+    }
+
+    #[test]
+    fn parse_geometry_rejects_empty_dimensions() {
+        assert_eq!(
+            parse_geometry("0x24"),
+            Err(String::from(
+                "Invalid geometry: Window dimensions should be greater than 0."
+            ))
+        );
+    }
 }
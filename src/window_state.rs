@@ -0,0 +1,158 @@
+use std::fs;
+use std::path::PathBuf;
+
+use log::{info, warn};
+
+use crate::command_line;
+use crate::paths::data_directory;
+
+// Where and how the window was last shown. It is saved whenever a window closes and restored for
+// the first window of the next start, unless --geometry asks for something else.
+//
+// The file holds one key=value pair per line. Sizes and positions are in screen coordinates, and
+// for maximized or fullscreen windows they are the ones to return to once the window is restored.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowState {
+    pub position: (i32, i32),
+    pub size: (u32, u32),
+    pub maximized: bool,
+    pub fullscreen: bool,
+    pub monitor: i32,
+}
+
+fn state_path() -> Option<PathBuf> {
+    data_directory().map(|directory| directory.join("window_state"))
+}
+
+impl WindowState {
+    fn parse(contents: &str) -> Option<WindowState> {
+        let values = contents
+            .lines()
+            .filter_map(|line| {
+                let mut parts = line.splitn(2, '=');
+                Some((parts.next()?.trim(), parts.next()?.trim()))
+            })
+            .collect::<Vec<(&str, &str)>>();
+
+        let value = |key: &str| {
+            values
+                .iter()
+                .find(|(value_key, _)| *value_key == key)
+                .map(|(_, value)| *value)
+        };
+
+        Some(WindowState {
+            position: (value("x")?.parse().ok()?, value("y")?.parse().ok()?),
+            size: (
+                value("width")?.parse().ok()?,
+                value("height")?.parse().ok()?,
+            ),
+            maximized: value("maximized")?.parse().ok()?,
+            fullscreen: value("fullscreen")?.parse().ok()?,
+            monitor: value("monitor")?.parse().ok()?,
+        })
+    }
+
+    fn serialize(&self) -> String {
+        format!(
+            "x={}\ny={}\nwidth={}\nheight={}\nmaximized={}\nfullscreen={}\nmonitor={}\n",
+            self.position.0,
+            self.position.1,
+            self.size.0,
+            self.size.1,
+            self.maximized,
+            self.fullscreen,
+            self.monitor
+        )
+    }
+
+    pub fn save(&self) {
+        let path = match state_path() {
+            Some(path) => path,
+            None => return,
+        };
+
+        let result = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&path, self.serialize()));
+        match result {
+            Ok(()) => info!("Window state saved to {:?}", path),
+            Err(error) => warn!("Could not save the window state to {:?}: {}", path, error),
+        }
+    }
+}
+
+// The saved state to restore, if there is one and --geometry doesn't override it.
+pub fn restorable_state() -> Option<WindowState> {
+    if command_line::option_value("--geometry").is_some() {
+        return None;
+    }
+
+    let path = state_path()?;
+    let contents = fs::read_to_string(&path).ok()?;
+    let state = WindowState::parse(&contents);
+    if state.is_none() {
+        warn!("Ignoring the malformed window state in {:?}", path);
+    }
+    state
+}
+
+pub fn maximized_requested() -> bool {
+    command_line::has_argument("--maximized")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state() -> WindowState {
+        WindowState {
+            position: (-1920, 40),
+            size: (1280, 720),
+            maximized: true,
+            fullscreen: false,
+            monitor: 1,
+        }
+    }
+
+    #[test]
+    fn parse_reads_what_serialize_writes() {
+        assert_eq!(WindowState::parse(&state().serialize()), Some(state()));
+    }
+
+    #[test]
+    fn parse_ignores_order_whitespace_and_unknown_keys() {
+        let contents = concat!(
+            "monitor = 1\n",
+            "fullscreen=false\n",
+            "version=2\n",
+            "height=720\n",
+            "width=1280\n",
+            "maximized=true\n",
+            "y=40\n",
+            "x=-1920\n",
+        );
+        assert_eq!(WindowState::parse(contents), Some(state()));
+    }
+
+    #[test]
+    fn parse_rejects_missing_keys() {
+        let contents = state().serialize().replace("monitor=1\n", "");
+        assert_eq!(WindowState::parse(&contents), None);
+    }
+
+    #[test]
+    fn parse_rejects_malformed_values() {
+        let contents = state().serialize().replace("width=1280", "width=-1280");
+        assert_eq!(WindowState::parse(&contents), None);
+
+        let contents = state()
+            .serialize()
+            .replace("maximized=true", "maximized=yes");
+        assert_eq!(WindowState::parse(&contents), None);
+
+        assert_eq!(WindowState::parse(""), None);
+        assert_eq!(WindowState::parse("not a window state"), None);
+    }
+}
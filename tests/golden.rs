@@ -0,0 +1,17 @@
+// Renders every case below tests/golden and compares it against the case's reference image.
+// Run with `cargo test --features golden-tests --test golden`, and add `-- --bless` to rewrite the
+// references after an intended rendering change.
+use std::path::Path;
+use std::process;
+
+use neovide::golden;
+
+fn main() {
+    let bless = std::env::args().any(|arg| arg == "--bless");
+    let cases_directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+
+    neovide::initialize_settings();
+    if !golden::run(&cases_directory, bless) {
+        process::exit(1);
+    }
+}